@@ -1,86 +1,47 @@
 #!/usr/bin/env ash
-if [ -z "${PORT}" ]; then
-    echo "Using 8080 as listening port"
-    PORT="8080"
-fi
-if [ -z "${API_ABUSEIPDB}" ]; then
-    echo "API Key for abuseipdb is required"
-    exit 1
-fi
-if [ -z "${EXPIRATION_TIME}" ]; then
-    EXPIRATION_TIME="14"
-fi
-if [ -z "${STALE_TIME}" ]; then
-    STALE_TIME="28"
-fi
+# Maps the variables of previous releases onto the BLACKLISTD_* variables
+# read by blacklistd. Every other option can be set using its BLACKLISTD_*
+# variable or a config file given by BLACKLISTD_CONFIG.
+export BLACKLISTD_PORT="${BLACKLISTD_PORT:-${PORT}}"
+export BLACKLISTD_API_ABUSEIPDB="${BLACKLISTD_API_ABUSEIPDB:-${API_ABUSEIPDB}}"
+export BLACKLISTD_EXPIRATION_DAYS="${BLACKLISTD_EXPIRATION_DAYS:-${EXPIRATION_TIME}}"
+export BLACKLISTD_STALE_DAYS="${BLACKLISTD_STALE_DAYS:-${STALE_TIME}}"
+export BLACKLISTD_DB_TYPE="${BLACKLISTD_DB_TYPE:-${DB_TYPE}}"
+export BLACKLISTD_DB_HOST="${BLACKLISTD_DB_HOST:-${DB_HOST}}"
+export BLACKLISTD_DB_PORT="${BLACKLISTD_DB_PORT:-${DB_PORT}}"
+export BLACKLISTD_DB_NAME="${BLACKLISTD_DB_NAME:-${DB_NAME}}"
+export BLACKLISTD_DB_USER="${BLACKLISTD_DB_USER:-${DB_USER}}"
+export BLACKLISTD_DB_PASS="${BLACKLISTD_DB_PASS:-${DB_PASS}}"
+export BLACKLISTD_DB_PATH="${BLACKLISTD_DB_PATH:-${DB_PATH}}"
 case ${LOG_LEVEL} in
     "0")
-    LOG_LEVEL="-vv"
+    export BLACKLISTD_LOG_LEVEL="trace"
     ;;
     "1")
-    LOG_LEVEL="-v"
+    export BLACKLISTD_LOG_LEVEL="debug"
     ;;
     "3")
-    LOG_LEVEL="-s"
+    export BLACKLISTD_LOG_LEVEL="warn"
     ;;
     "4")
-    LOG_LEVEL="-ss"
+    export BLACKLISTD_LOG_LEVEL="error"
     ;;
     "5")
-    LOG_LEVEL="-sss"
-    ;;
-    *)
-    LOG_LEVEL=""
+    export BLACKLISTD_LOG_LEVEL="off"
     ;;
 esac
-if [ "${DB_TYPE}" == "mysql" ]; then
-    echo "Using mysql as database backend."
-    if [ -z "${DB_USER}" ] || [ -z "${DB_PASS}" ] || 
-        [ -z "${DB_HOST}" ] || [ -z "${DB_PORT}" ] || 
-        [ -z "${DB_NAME}" ]; then
-        echo "The following env variables are required to use mysql as backend:"
-        echo "DB_USER, DB_PASS, DB_HOST, DB_PORT, DB_NAME"
-        exit 1
-    fi
-
-    diesel migration run \
-        --migration-dir='/usr/share/blacklistd/migrations.mysql' \
-        --database-url="mysql://${DB_USER}:${DB_PASS}@${DB_HOST}:${DB_PORT}/${DB_NAME}"
-    blacklistd --api-abuseipdb "${API_ABUSEIPDB}" --port "${PORT}" ${LOG_LEVEL} \
-        --db-type mysql --db-host "${DB_HOST}" --db-port "${DB_PORT}" \
-        --db-name "${DB_NAME}" --db-user "${DB_USER}" --db-pass "${DB_PASS}" \
-        --expiration-days "${EXPIRATION_TIME}" --stale-days "${STALE_TIME}"
-elif [ "${DB_TYPE}" == "postgres" ]; then 
-    echo "Using postgres as database backend."
-    if [ -z "${DB_USER}" ] || [ -z "${DB_PASS}" ] || 
-        [ -z "${DB_HOST}" ] || [ -z "${DB_PORT}" ] || 
-        [ -z "${DB_NAME}" ]; then
-        echo "The following env variables are required to use postgres as backend:"
-        echo "DB_USER, DB_PASS, DB_HOST, DB_PORT, DB_NAME"
-        exit 1
-    fi
-
-    diesel migration run \
-        --migration-dir='/usr/share/blacklistd/migrations.postgres' \
-        --database-url="postgres://${DB_USER}:${DB_PASS}@${DB_HOST}:${DB_PORT}/${DB_NAME}"
-    blacklistd --api-abuseipdb "${API_ABUSEIPDB}" --port "${PORT}" ${LOG_LEVEL} \
-        --db-type postgres --db-host "${DB_HOST}" --db-port "${DB_PORT}" \
-        --db-name "${DB_NAME}" --db-user "${DB_USER}" --db-pass "${DB_PASS}" \
-        --expiration-days "${EXPIRATION_TIME}" --stale-days "${STALE_TIME}"
-else
+if [ -z "${BLACKLISTD_CONFIG}" ] && [ -z "${BLACKLISTD_DB_TYPE}" ]; then
     echo "Using sqlite as database backend."
     echo "Consider using a different backend to improve performance"
-    if [ -z "${DB_PATH}" ]; then
-        echo "Using /data/db.sqlite as default path"
-        DB_PATH="/data/db.sqlite"
-    fi
-    DB_DIR=$(dirname "${DB_PATH}")
-    mkdir -p "${DB_DIR}"
-
-    diesel migration run \
-        --migration-dir='/usr/share/blacklistd/migrations.sqlite' \
-        --database-url="${DB_PATH}"
-    blacklistd --api-abuseipdb "${API_ABUSEIPDB}" --port "${PORT}" ${LOG_LEVEL} \
-        --db-type sqlite --db-path "${DB_PATH}" \
-        --expiration-days "${EXPIRATION_TIME}" --stale-days "${STALE_TIME}"
+    export BLACKLISTD_DB_TYPE="sqlite"
+fi
+if [ "${BLACKLISTD_DB_TYPE}" == "sqlite" ] && [ -z "${BLACKLISTD_DB_PATH}" ] &&
+    [ -z "${BLACKLISTD_DB_URL}" ]; then
+    echo "Using /data/db.sqlite as default path"
+    export BLACKLISTD_DB_PATH="/data/db.sqlite"
 fi
+if [ -n "${BLACKLISTD_DB_PATH}" ]; then
+    mkdir -p "$(dirname "${BLACKLISTD_DB_PATH}")"
+fi
+
+exec blacklistd
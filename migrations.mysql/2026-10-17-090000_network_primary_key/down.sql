@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DELETE b FROM blacklist b
+    JOIN blacklist h
+    ON b.ip = h.ip AND b.ip_type = h.ip_type AND b.prefix < h.prefix;
+
+ALTER TABLE blacklist DROP PRIMARY KEY, ADD PRIMARY KEY (ip, ip_type);
+ALTER TABLE blacklist DROP COLUMN prefix;
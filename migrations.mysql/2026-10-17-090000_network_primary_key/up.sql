@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE blacklist ADD COLUMN prefix SMALLINT NOT NULL DEFAULT 128;
+UPDATE blacklist SET prefix = 32 WHERE ip_type = 0;
+
+ALTER TABLE blacklist DROP PRIMARY KEY, ADD PRIMARY KEY (ip, ip_type, prefix);
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TABLE api_token (
+    name VARCHAR(255) NOT NULL,
+    token_hash CHAR(64) NOT NULL,
+    scope SMALLINT NOT NULL,
+    created timestamp NOT NULL DEFAULT current_timestamp,
+    PRIMARY KEY(name),
+    UNIQUE(token_hash)
+);
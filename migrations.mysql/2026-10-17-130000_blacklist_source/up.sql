@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE blacklist_source (
+    ip VARBINARY(16) NOT NULL,
+    ip_type SMALLINT NOT NULL,
+    prefix SMALLINT NOT NULL,
+    backend_type SMALLINT NOT NULL,
+    last_update timestamp NOT NULL DEFAULT current_timestamp,
+    expires timestamp NULL DEFAULT NULL,
+    PRIMARY KEY(ip, ip_type, prefix, backend_type)
+);
+CREATE INDEX blacklist_source_last_update ON blacklist_source (last_update);
+
+INSERT INTO blacklist_source (ip, ip_type, prefix, backend_type, last_update, expires)
+    SELECT ip, ip_type, prefix, backend_type, last_update, expires FROM blacklist;
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE blacklist_history (
+    id BIGINT NOT NULL AUTO_INCREMENT,
+    ip VARBINARY(16) NOT NULL,
+    ip_type SMALLINT NOT NULL,
+    prefix SMALLINT NOT NULL,
+    backend_type SMALLINT NOT NULL,
+    event SMALLINT NOT NULL,
+    score SMALLINT,
+    created timestamp NOT NULL DEFAULT current_timestamp,
+    PRIMARY KEY(id)
+);
+CREATE INDEX blacklist_history_ip ON blacklist_history (ip, ip_type);
+CREATE INDEX blacklist_history_created ON blacklist_history (created);
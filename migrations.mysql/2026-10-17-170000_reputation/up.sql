@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE blacklist ADD COLUMN score smallint NULL DEFAULT NULL;
+ALTER TABLE blacklist ADD COLUMN total_reports integer NULL DEFAULT NULL;
+ALTER TABLE blacklist ADD COLUMN last_reported timestamp NULL DEFAULT NULL;
+ALTER TABLE blacklist ADD COLUMN country_code VARCHAR(2) NULL DEFAULT NULL;
+ALTER TABLE blacklist ADD COLUMN usage_type VARCHAR(64) NULL DEFAULT NULL;
@@ -0,0 +1,21 @@
+-- Your SQL goes here
+ALTER TABLE blacklist_source ADD COLUMN reason VARCHAR(255) NULL DEFAULT NULL;
+ALTER TABLE blacklist_source ADD COLUMN score smallint NULL DEFAULT NULL;
+ALTER TABLE blacklist_source ADD COLUMN total_reports integer NULL DEFAULT NULL;
+ALTER TABLE blacklist_source ADD COLUMN last_reported timestamp NULL DEFAULT NULL;
+ALTER TABLE blacklist_source ADD COLUMN country_code VARCHAR(2) NULL DEFAULT NULL;
+ALTER TABLE blacklist_source ADD COLUMN usage_type VARCHAR(64) NULL DEFAULT NULL;
+
+UPDATE blacklist_source
+JOIN blacklist
+    ON blacklist.ip = blacklist_source.ip
+    AND blacklist.ip_type = blacklist_source.ip_type
+    AND blacklist.prefix = blacklist_source.prefix
+    AND blacklist.backend_type = blacklist_source.backend_type
+SET
+    blacklist_source.reason = blacklist.reason,
+    blacklist_source.score = blacklist.score,
+    blacklist_source.total_reports = blacklist.total_reports,
+    blacklist_source.last_reported = blacklist.last_reported,
+    blacklist_source.country_code = blacklist.country_code,
+    blacklist_source.usage_type = blacklist.usage_type;
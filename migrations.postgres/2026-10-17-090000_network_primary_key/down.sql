@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM blacklist b
+    USING blacklist h
+    WHERE b.ip = h.ip AND b.ip_type = h.ip_type AND b.prefix < h.prefix;
+
+ALTER TABLE blacklist DROP CONSTRAINT blacklist_pkey;
+ALTER TABLE blacklist ADD PRIMARY KEY (ip, ip_type);
+ALTER TABLE blacklist DROP COLUMN prefix;
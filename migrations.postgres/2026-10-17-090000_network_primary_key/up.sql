@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE blacklist ADD COLUMN prefix SMALLINT NOT NULL DEFAULT 128;
+UPDATE blacklist SET prefix = 32 WHERE ip_type = 0;
+
+ALTER TABLE blacklist DROP CONSTRAINT blacklist_pkey;
+ALTER TABLE blacklist ADD PRIMARY KEY (ip, ip_type, prefix);
@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE blacklist ADD COLUMN expires timestamp NULL;
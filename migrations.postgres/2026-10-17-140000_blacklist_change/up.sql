@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE blacklist_change (
+    id BIGSERIAL NOT NULL,
+    ip bytea NOT NULL,
+    ip_type SMALLINT NOT NULL,
+    prefix SMALLINT NOT NULL,
+    change_type SMALLINT NOT NULL,
+    created timestamp NOT NULL DEFAULT current_timestamp,
+    PRIMARY KEY(id)
+);
+CREATE INDEX blacklist_change_created ON blacklist_change (created);
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE rate_limit (
+    backend_type SMALLINT NOT NULL,
+    endpoint TEXT NOT NULL,
+    request_limit INTEGER,
+    remaining INTEGER,
+    reset timestamp NULL,
+    updated timestamp NOT NULL DEFAULT current_timestamp,
+    PRIMARY KEY(backend_type, endpoint)
+);
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE blacklist ADD COLUMN score smallint NULL;
+ALTER TABLE blacklist ADD COLUMN total_reports integer NULL;
+ALTER TABLE blacklist ADD COLUMN last_reported timestamp NULL;
+ALTER TABLE blacklist ADD COLUMN country_code TEXT NULL;
+ALTER TABLE blacklist ADD COLUMN usage_type TEXT NULL;
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE blacklist_source DROP COLUMN usage_type;
+ALTER TABLE blacklist_source DROP COLUMN country_code;
+ALTER TABLE blacklist_source DROP COLUMN last_reported;
+ALTER TABLE blacklist_source DROP COLUMN total_reports;
+ALTER TABLE blacklist_source DROP COLUMN score;
+ALTER TABLE blacklist_source DROP COLUMN reason;
@@ -0,0 +1,20 @@
+-- Your SQL goes here
+ALTER TABLE blacklist_source ADD COLUMN reason TEXT NULL;
+ALTER TABLE blacklist_source ADD COLUMN score smallint NULL;
+ALTER TABLE blacklist_source ADD COLUMN total_reports integer NULL;
+ALTER TABLE blacklist_source ADD COLUMN last_reported timestamp NULL;
+ALTER TABLE blacklist_source ADD COLUMN country_code TEXT NULL;
+ALTER TABLE blacklist_source ADD COLUMN usage_type TEXT NULL;
+
+UPDATE blacklist_source SET
+    reason = blacklist.reason,
+    score = blacklist.score,
+    total_reports = blacklist.total_reports,
+    last_reported = blacklist.last_reported,
+    country_code = blacklist.country_code,
+    usage_type = blacklist.usage_type
+FROM blacklist
+WHERE blacklist.ip = blacklist_source.ip
+    AND blacklist.ip_type = blacklist_source.ip_type
+    AND blacklist.prefix = blacklist_source.prefix
+    AND blacklist.backend_type = blacklist_source.backend_type;
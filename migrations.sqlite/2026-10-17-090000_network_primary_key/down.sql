@@ -0,0 +1,21 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX blacklist_backend_type;
+DROP INDEX blacklist_last_update;
+ALTER TABLE blacklist RENAME TO blacklist_old;
+
+CREATE TABLE blacklist (
+    ip VARBINARY(16) NOT NULL,
+    ip_type SMALLINT NOT NULL,
+    backend_type SMALLINT NOT NULL,
+    last_update timestamp NOT NULL DEFAULT current_timestamp,
+    reason TEXT,
+    PRIMARY KEY(ip, ip_type)
+);
+
+INSERT OR IGNORE INTO blacklist (ip, ip_type, backend_type, last_update, reason)
+    SELECT ip, ip_type, backend_type, last_update, reason FROM blacklist_old
+    ORDER BY prefix DESC;
+DROP TABLE blacklist_old;
+
+CREATE INDEX blacklist_backend_type ON blacklist (backend_type);
+CREATE INDEX blacklist_last_update ON blacklist (last_update);
@@ -0,0 +1,23 @@
+-- Your SQL goes here
+DROP INDEX blacklist_backend_type;
+DROP INDEX blacklist_last_update;
+ALTER TABLE blacklist RENAME TO blacklist_old;
+
+CREATE TABLE blacklist (
+    ip VARBINARY(16) NOT NULL,
+    ip_type SMALLINT NOT NULL,
+    backend_type SMALLINT NOT NULL,
+    last_update timestamp NOT NULL DEFAULT current_timestamp,
+    prefix SMALLINT NOT NULL DEFAULT 128,
+    reason TEXT,
+    PRIMARY KEY(ip, ip_type, prefix)
+);
+
+INSERT INTO blacklist (ip, ip_type, backend_type, last_update, prefix, reason)
+    SELECT ip, ip_type, backend_type, last_update,
+        CASE WHEN ip_type = 0 THEN 32 ELSE 128 END, reason
+    FROM blacklist_old;
+DROP TABLE blacklist_old;
+
+CREATE INDEX blacklist_backend_type ON blacklist (backend_type);
+CREATE INDEX blacklist_last_update ON blacklist (last_update);
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE allowlist (
+    ip VARBINARY(16) NOT NULL,
+    ip_type SMALLINT NOT NULL,
+    prefix SMALLINT NOT NULL,
+    comment TEXT,
+    created_by TEXT,
+    created timestamp NOT NULL DEFAULT current_timestamp,
+    PRIMARY KEY(ip, ip_type, prefix)
+);
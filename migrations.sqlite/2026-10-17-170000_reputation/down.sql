@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE blacklist DROP COLUMN usage_type;
+ALTER TABLE blacklist DROP COLUMN country_code;
+ALTER TABLE blacklist DROP COLUMN last_reported;
+ALTER TABLE blacklist DROP COLUMN total_reports;
+ALTER TABLE blacklist DROP COLUMN score;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE blacklist ADD COLUMN score smallint;
+ALTER TABLE blacklist ADD COLUMN total_reports integer;
+ALTER TABLE blacklist ADD COLUMN last_reported timestamp;
+ALTER TABLE blacklist ADD COLUMN country_code TEXT;
+ALTER TABLE blacklist ADD COLUMN usage_type TEXT;
@@ -0,0 +1,51 @@
+-- Your SQL goes here
+ALTER TABLE blacklist_source ADD COLUMN reason TEXT;
+ALTER TABLE blacklist_source ADD COLUMN score smallint;
+ALTER TABLE blacklist_source ADD COLUMN total_reports integer;
+ALTER TABLE blacklist_source ADD COLUMN last_reported timestamp;
+ALTER TABLE blacklist_source ADD COLUMN country_code TEXT;
+ALTER TABLE blacklist_source ADD COLUMN usage_type TEXT;
+
+UPDATE blacklist_source SET
+    reason = (
+        SELECT blacklist.reason FROM blacklist
+        WHERE blacklist.ip = blacklist_source.ip
+            AND blacklist.ip_type = blacklist_source.ip_type
+            AND blacklist.prefix = blacklist_source.prefix
+            AND blacklist.backend_type = blacklist_source.backend_type
+    ),
+    score = (
+        SELECT blacklist.score FROM blacklist
+        WHERE blacklist.ip = blacklist_source.ip
+            AND blacklist.ip_type = blacklist_source.ip_type
+            AND blacklist.prefix = blacklist_source.prefix
+            AND blacklist.backend_type = blacklist_source.backend_type
+    ),
+    total_reports = (
+        SELECT blacklist.total_reports FROM blacklist
+        WHERE blacklist.ip = blacklist_source.ip
+            AND blacklist.ip_type = blacklist_source.ip_type
+            AND blacklist.prefix = blacklist_source.prefix
+            AND blacklist.backend_type = blacklist_source.backend_type
+    ),
+    last_reported = (
+        SELECT blacklist.last_reported FROM blacklist
+        WHERE blacklist.ip = blacklist_source.ip
+            AND blacklist.ip_type = blacklist_source.ip_type
+            AND blacklist.prefix = blacklist_source.prefix
+            AND blacklist.backend_type = blacklist_source.backend_type
+    ),
+    country_code = (
+        SELECT blacklist.country_code FROM blacklist
+        WHERE blacklist.ip = blacklist_source.ip
+            AND blacklist.ip_type = blacklist_source.ip_type
+            AND blacklist.prefix = blacklist_source.prefix
+            AND blacklist.backend_type = blacklist_source.backend_type
+    ),
+    usage_type = (
+        SELECT blacklist.usage_type FROM blacklist
+        WHERE blacklist.ip = blacklist_source.ip
+            AND blacklist.ip_type = blacklist_source.ip_type
+            AND blacklist.prefix = blacklist_source.prefix
+            AND blacklist.backend_type = blacklist_source.backend_type
+    );
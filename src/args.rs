@@ -1,10 +1,33 @@
-use crate::middleware::diesel::DBType;
+use crate::{
+    backend::{
+        abuseipdb::BlacklistQuery, provider::Provider,
+        schedule::Schedule, spamhaus::Spamhaus,
+        url_list::UrlList,
+    },
+    config::ConfigFile,
+    helper::parse_network,
+    middleware::diesel::DBType,
+    types::{
+        allowlist::Allowlist, api_token::TokenScope,
+        backend_type::BackendType,
+    },
+};
 use clap::{
     app_from_crate, crate_authors, crate_description, crate_name,
-    crate_version, Arg, ArgMatches,
+    crate_version, AppSettings, Arg, ArgMatches, SubCommand,
 };
 use log::{error, Level};
-use std::process::exit;
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    process::exit,
+    str::FromStr,
+};
+
+const ENV_PREFIX: &str = "BLACKLISTD_";
+
+const ARGS_CONFIG: &str = "config";
+const ARGS_CONFIG_ENV: &str = "BLACKLISTD_CONFIG";
 
 const ARGS_LISTEN: &str = "listen";
 const ARGS_LISTEN_DEFAULT: &str = "::";
@@ -13,6 +36,7 @@ const ARGS_PORT_DEFAULT: &str = "8080";
 const ARGS_PORT_DEFAULT_U16: u16 = 8080;
 const ARGS_VERBOSE: &str = "verbose";
 const ARGS_SILENT: &str = "silent";
+const ARGS_LOG_LEVEL: &str = "log-level";
 
 const ARGS_EXPIRATION_DAYS: &str = "expiration-days";
 const ARGS_EXPIRATION_DAYS_DEFAULT: &str = "14";
@@ -20,6 +44,20 @@ const ARGS_EXPIRATION_DAYS_DEFAULT_U32: u32 = 14;
 const ARGS_STALE_DAYS: &str = "stale-days";
 const ARGS_STALE_DAYS_DEFAULT: &str = "28";
 const ARGS_STALE_DAYS_DEFAULT_U32: u32 = 28;
+const ARGS_HISTORY_DAYS: &str = "history-days";
+const ARGS_HISTORY_DAYS_DEFAULT: &str = "90";
+const ARGS_HISTORY_DAYS_DEFAULT_U32: u32 = 90;
+const ARGS_NO_RESERVED_FILTER: &str = "no-reserved-filter";
+const ARGS_NO_ANONYMOUS_READ: &str = "no-anonymous-read";
+const ARGS_NO_MIGRATE: &str = "no-migrate";
+const ARGS_SCHEDULE: &str = "schedule";
+const ARGS_SCHEDULE_DEFAULT: &str = "0 0 * * *";
+const ARGS_SCHEDULE_JITTER: &str = "schedule-jitter";
+const ARGS_SCHEDULE_JITTER_DEFAULT: &str = "300";
+const ARGS_SCHEDULE_JITTER_DEFAULT_U32: u32 = 300;
+const ARGS_RECHECK_BUDGET: &str = "recheck-budget";
+const ARGS_RECHECK_BUDGET_DEFAULT: &str = "1000";
+const ARGS_RECHECK_BUDGET_DEFAULT_U32: u32 = 1000;
 
 const ARGS_DATABASE_TYPE: &str = "db-type";
 const ARGS_DATABASE_HOST: &str = "db-host";
@@ -28,17 +66,104 @@ const ARGS_DATABASE_NAME: &str = "db-name";
 const ARGS_DATABASE_USER: &str = "db-user";
 const ARGS_DATABASE_PASS: &str = "db-pass";
 const ARGS_DATABASE_PATH: &str = "db-path";
+const ARGS_DATABASE_URL: &str = "db-url";
+const ARGS_DATABASE_POOL_SIZE: &str = "db-pool-size";
+const ARGS_DATABASE_POOL_SIZE_DEFAULT: &str = "10";
+const ARGS_DATABASE_POOL_SIZE_DEFAULT_U32: u32 = 10;
+const ARGS_DATABASE_CONNECTION_TIMEOUT: &str =
+    "db-connection-timeout";
+const ARGS_DATABASE_CONNECTION_TIMEOUT_DEFAULT: &str = "30";
+const ARGS_DATABASE_CONNECTION_TIMEOUT_DEFAULT_U32: u32 = 30;
+const ARGS_DATABASE_IDLE_TIMEOUT: &str = "db-idle-timeout";
+const ARGS_DATABASE_IDLE_TIMEOUT_DEFAULT: &str = "600";
+const ARGS_DATABASE_IDLE_TIMEOUT_DEFAULT_U32: u32 = 600;
 
 const ARGS_BACKEND_ABUSEIPDB: &str = "api-abuseipdb";
+const ARGS_ABUSEIPDB_CONFIDENCE: &str = "abuseipdb-confidence";
+const ARGS_ABUSEIPDB_CONFIDENCE_DEFAULT: &str = "100";
+const ARGS_ABUSEIPDB_CONFIDENCE_DEFAULT_U8: u8 = 100;
+const ARGS_ABUSEIPDB_LIMIT: &str = "abuseipdb-limit";
+const ARGS_ABUSEIPDB_ONLY_COUNTRIES: &str =
+    "abuseipdb-only-countries";
+const ARGS_ABUSEIPDB_EXCEPT_COUNTRIES: &str =
+    "abuseipdb-except-countries";
+const ARGS_ABUSEIPDB_IP_VERSION: &str = "abuseipdb-ip-version";
+const ARGS_BACKEND_URL_LIST: &str = "url-list";
+const ARGS_BACKEND_SPAMHAUS: &str = "spamhaus";
+
+const CMD_MIGRATE: &str = "migrate";
+const ARGS_MIGRATE_DRY_RUN: &str = "dry-run";
+const CMD_UPDATE: &str = "update";
+const ARGS_UPDATE_PROVIDER: &str = "provider";
+const CMD_LIST: &str = "list";
+const CMD_ADD: &str = "add";
+const CMD_REMOVE: &str = "remove";
+const CMD_CHECK: &str = "check";
+const CMD_STATS: &str = "stats";
+const CMD_FSCK: &str = "fsck";
+const ARGS_NETWORK: &str = "network";
+const ARGS_ADDRESS: &str = "ip";
+const ARGS_TTL: &str = "ttl";
+const ARGS_REASON: &str = "reason";
+const CMD_TOKEN: &str = "token";
+const CMD_TOKEN_CREATE: &str = "create";
+const CMD_TOKEN_REVOKE: &str = "revoke";
+const CMD_TOKEN_LIST: &str = "list";
+const ARGS_TOKEN_NAME: &str = "name";
+const ARGS_TOKEN_SCOPE: &str = "scope";
+const ARGS_TOKEN_SCOPE_DEFAULT: &str = "read";
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Serve,
+    Migrate { dry_run: bool },
+    Update { providers: Vec<String> },
+    Admin(AdminCommand),
+    Token(TokenCommand),
+}
+
+#[derive(Debug, Clone)]
+pub enum AdminCommand {
+    List,
+    Add {
+        network: String,
+        ttl: Option<u32>,
+        reason: Option<String>,
+    },
+    Remove {
+        network: String,
+    },
+    Check {
+        address: String,
+    },
+    Stats,
+    Fsck,
+}
+
+#[derive(Debug, Clone)]
+pub enum TokenCommand {
+    Create { name: String, scope: TokenScope },
+    Revoke { name: String },
+    List,
+}
 
 #[derive(Debug, Clone)]
 pub struct CliArguments {
+    pub command: Command,
+
     pub listen: String,
     pub port: u16,
-    pub log_level: Option<Level>,
 
     pub expiration_days: u32,
     pub stale_days: u32,
+    pub history_days: u32,
+    pub reserved_filter: bool,
+    pub anonymous_read: bool,
+    pub auto_migrate: bool,
+    pub schedule: Schedule,
+    pub schedule_jitter: u32,
+    pub recheck_budget: u32,
+    pub provider_schedules: HashMap<BackendType, Schedule>,
 
     pub db_type: DBType,
     pub db_host: String,
@@ -47,24 +172,58 @@ pub struct CliArguments {
     pub db_user: String,
     pub db_pass: String,
     pub db_path: String,
+    pub db_url: Option<String>,
+    pub db_pool_size: u32,
+    pub db_connection_timeout: u32,
+    pub db_idle_timeout: u32,
 
     pub api_abuseipdb: Option<String>,
+    pub abuseipdb_query: BlacklistQuery,
+    pub url_lists: Vec<UrlList>,
+    pub spamhaus: Vec<Spamhaus>,
+    pub allowlist: Vec<Allowlist>,
+}
+
+impl CliArguments {
+    /// The schedule of the given provider. Falls back to the global
+    /// schedule.
+    pub fn schedule_of(
+        &self,
+        backend_type: BackendType,
+    ) -> &Schedule {
+        self.provider_schedules
+            .get(&backend_type)
+            .unwrap_or(&self.schedule)
+    }
 }
 
 pub fn get_arguments() -> CliArguments {
     let matches = get_cli_config();
-    let listen = matches
-        .value_of(ARGS_LISTEN)
-        .unwrap_or(ARGS_LISTEN_DEFAULT)
-        .into();
-    let port: u16 = matches
-        .value_of(ARGS_PORT)
-        .and_then(|port| port.parse().ok())
-        .unwrap_or(ARGS_PORT_DEFAULT_U16);
+    let file = match matches.value_of(ARGS_CONFIG) {
+        Some(path) => match ConfigFile::load(path) {
+            Ok(file) => file,
+            Err(err) => {
+                // logging is configured by the file, so it is not
+                // available yet
+                eprintln!(
+                    "Unable to load config {}: {}",
+                    path, err
+                );
+                exit(1);
+            }
+        },
+        None => ConfigFile::default(),
+    };
+    let settings = Settings {
+        matches: &matches,
+        file,
+    };
+
     let log_level = match (
         matches.occurrences_of(ARGS_SILENT),
         matches.occurrences_of(ARGS_VERBOSE),
     ) {
+        (0, 0) => settings.log_level(),
         (0, 1) => Some(Level::Debug),
         (0, v) if v > 1 => Some(Level::Trace),
         (1, 0) => Some(Level::Warn),
@@ -72,82 +231,402 @@ pub fn get_arguments() -> CliArguments {
         (s, 0) if s > 2 => None,
         _ => Some(Level::Info),
     };
+    if let Some(log_level) = log_level {
+        simple_logger::init_with_level(log_level).unwrap();
+    }
 
-    let expiration_days: u32 = matches
-        .value_of(ARGS_EXPIRATION_DAYS)
-        .and_then(|e| e.parse().ok())
+    let command = match matches.subcommand() {
+        (CMD_MIGRATE, Some(matches)) => Command::Migrate {
+            dry_run: matches.is_present(ARGS_MIGRATE_DRY_RUN),
+        },
+        (CMD_UPDATE, Some(matches)) => Command::Update {
+            providers: matches
+                .values_of(ARGS_UPDATE_PROVIDER)
+                .map(|values| values.map(String::from).collect())
+                .unwrap_or_default(),
+        },
+        (CMD_LIST, Some(_)) => Command::Admin(AdminCommand::List),
+        (CMD_ADD, Some(matches)) => {
+            let ttl = matches.value_of(ARGS_TTL).map(|ttl| {
+                match ttl.parse() {
+                    Ok(ttl) => ttl,
+                    Err(_) => {
+                        error!(
+                            "Invalid value \"{}\" for {}",
+                            ttl, ARGS_TTL
+                        );
+                        exit(1);
+                    }
+                }
+            });
+            Command::Admin(AdminCommand::Add {
+                network: matches
+                    .value_of(ARGS_NETWORK)
+                    .unwrap_or_default()
+                    .into(),
+                ttl,
+                reason: matches
+                    .value_of(ARGS_REASON)
+                    .map(String::from),
+            })
+        }
+        (CMD_REMOVE, Some(matches)) => {
+            Command::Admin(AdminCommand::Remove {
+                network: matches
+                    .value_of(ARGS_NETWORK)
+                    .unwrap_or_default()
+                    .into(),
+            })
+        }
+        (CMD_CHECK, Some(matches)) => {
+            Command::Admin(AdminCommand::Check {
+                address: matches
+                    .value_of(ARGS_ADDRESS)
+                    .unwrap_or_default()
+                    .into(),
+            })
+        }
+        (CMD_STATS, Some(_)) => {
+            Command::Admin(AdminCommand::Stats)
+        }
+        (CMD_FSCK, Some(_)) => Command::Admin(AdminCommand::Fsck),
+        (CMD_TOKEN, Some(matches)) => {
+            Command::Token(get_token_command(matches))
+        }
+        _ => Command::Serve,
+    };
+    let listen = settings
+        .value(ARGS_LISTEN)
+        .unwrap_or_else(|| ARGS_LISTEN_DEFAULT.into());
+    let port: u16 = settings
+        .parse(ARGS_PORT)
+        .unwrap_or(ARGS_PORT_DEFAULT_U16);
+
+    let expiration_days: u32 = settings
+        .parse(ARGS_EXPIRATION_DAYS)
         .unwrap_or(ARGS_EXPIRATION_DAYS_DEFAULT_U32);
-    let stale_days: u32 = matches
-        .value_of(ARGS_STALE_DAYS)
-        .and_then(|e| e.parse().ok())
+    let stale_days: u32 = settings
+        .parse(ARGS_STALE_DAYS)
         .unwrap_or(ARGS_STALE_DAYS_DEFAULT_U32);
+    if stale_days <= expiration_days {
+        error!(
+            "Stale days ({}) must be greater than expiration days ({}). Otherwise ips are removed before being rechecked",
+            stale_days, expiration_days
+        );
+        exit(1);
+    }
+    let history_days: u32 = settings
+        .parse(ARGS_HISTORY_DAYS)
+        .unwrap_or(ARGS_HISTORY_DAYS_DEFAULT_U32);
+    let reserved_filter = !settings.disabled(
+        ARGS_NO_RESERVED_FILTER,
+        settings.file.reserved_filter,
+    );
+    let anonymous_read = !settings.disabled(
+        ARGS_NO_ANONYMOUS_READ,
+        settings.file.anonymous_read,
+    );
+    let auto_migrate = !settings
+        .disabled(ARGS_NO_MIGRATE, settings.file.auto_migrate);
+    let schedule = parse_schedule(
+        &settings.value(ARGS_SCHEDULE).unwrap_or_else(|| {
+            String::from(ARGS_SCHEDULE_DEFAULT)
+        }),
+    );
+    let schedule_jitter: u32 = settings
+        .parse(ARGS_SCHEDULE_JITTER)
+        .unwrap_or(ARGS_SCHEDULE_JITTER_DEFAULT_U32);
+    let recheck_budget: u32 = settings
+        .parse(ARGS_RECHECK_BUDGET)
+        .unwrap_or(ARGS_RECHECK_BUDGET_DEFAULT_U32);
 
-    let db_type = match matches
-        .value_of(ARGS_DATABASE_TYPE)
-        .and_then(|t| DBType::parse(t))
-    {
-        Some(v) => v,
+    let db_type = match settings.value(ARGS_DATABASE_TYPE) {
+        Some(value) => match DBType::parse(&value) {
+            Some(v) => v,
+            None => {
+                error!("Unknown Database Type \"{}\"", value);
+                exit(1);
+            }
+        },
         None => {
             error!("Database Type is required");
             exit(1);
         }
     };
-    let db_host = match matches.value_of(ARGS_DATABASE_HOST) {
-        Some(v) => v.into(),
-        None => {
-            error!("Database Host is required");
-            exit(1);
-        }
+    let db_url = settings.value(ARGS_DATABASE_URL);
+    let (network_required, path_required) = match db_type {
+        _ if db_url.is_some() => (false, false),
+        DBType::SQLITE => (false, true),
+        _ => (true, false),
     };
-    let db_port = match matches
-        .value_of(ARGS_DATABASE_PORT)
-        .and_then(|port| port.parse().ok())
-    {
+    let db_host = settings.required(
+        ARGS_DATABASE_HOST,
+        "Database Host",
+        network_required,
+    );
+    let db_port = match settings.parse(ARGS_DATABASE_PORT) {
         Some(v) => v,
+        None if !network_required => 0,
         None => {
             error!("Database Port is required");
             exit(1);
         }
     };
-    let db_name = match matches.value_of(ARGS_DATABASE_NAME) {
-        Some(v) => v.into(),
-        None => {
-            error!("Database Name is required");
-            exit(1);
-        }
-    };
-    let db_user = match matches.value_of(ARGS_DATABASE_USER) {
-        Some(v) => v.into(),
-        None => {
-            error!("Database User is required");
-            exit(1);
-        }
-    };
-    let db_pass = match matches.value_of(ARGS_DATABASE_PASS) {
-        Some(v) => v.into(),
-        None => {
-            error!("Database Password is required");
-            exit(1);
-        }
+    let db_name = settings.required(
+        ARGS_DATABASE_NAME,
+        "Database Name",
+        network_required,
+    );
+    let db_user = settings.required(
+        ARGS_DATABASE_USER,
+        "Database User",
+        network_required,
+    );
+    let db_pass = settings.required(
+        ARGS_DATABASE_PASS,
+        "Database Password",
+        network_required,
+    );
+    let db_path = settings.required(
+        ARGS_DATABASE_PATH,
+        "Database Path",
+        path_required,
+    );
+    let db_pool_size: u32 = settings
+        .parse(ARGS_DATABASE_POOL_SIZE)
+        .unwrap_or(ARGS_DATABASE_POOL_SIZE_DEFAULT_U32);
+    if db_pool_size == 0 {
+        error!("Database Pool Size must be greater than 0");
+        exit(1);
+    }
+    let db_connection_timeout: u32 = settings
+        .parse(ARGS_DATABASE_CONNECTION_TIMEOUT)
+        .unwrap_or(ARGS_DATABASE_CONNECTION_TIMEOUT_DEFAULT_U32);
+    if db_connection_timeout == 0 {
+        error!(
+            "Database Connection Timeout must be greater than 0"
+        );
+        exit(1);
+    }
+    let db_idle_timeout: u32 = settings
+        .parse(ARGS_DATABASE_IDLE_TIMEOUT)
+        .unwrap_or(ARGS_DATABASE_IDLE_TIMEOUT_DEFAULT_U32);
+
+    let api_abuseipdb = settings.value(ARGS_BACKEND_ABUSEIPDB);
+    let abuseipdb_confidence: u8 = settings
+        .parse(ARGS_ABUSEIPDB_CONFIDENCE)
+        .unwrap_or(ARGS_ABUSEIPDB_CONFIDENCE_DEFAULT_U8);
+    if !(25..=100).contains(&abuseipdb_confidence) {
+        error!(
+            "The abuseipdb confidence ({}) has to be between 25 and 100",
+            abuseipdb_confidence
+        );
+        exit(1);
+    }
+    let abuseipdb_limit: Option<u32> =
+        settings.parse(ARGS_ABUSEIPDB_LIMIT);
+    if abuseipdb_limit == Some(0) {
+        error!("The abuseipdb limit has to be greater than 0");
+        exit(1);
+    }
+    let abuseipdb_ip_version: Option<u8> =
+        settings.parse(ARGS_ABUSEIPDB_IP_VERSION);
+    if let Some(version) =
+        abuseipdb_ip_version.filter(|v| *v != 4 && *v != 6)
+    {
+        error!(
+            "The abuseipdb ip version ({}) has to be 4 or 6",
+            version
+        );
+        exit(1);
+    }
+    let abuseipdb_query = BlacklistQuery {
+        confidence: abuseipdb_confidence,
+        limit: abuseipdb_limit,
+        only_countries: parse_countries(
+            &settings,
+            ARGS_ABUSEIPDB_ONLY_COUNTRIES,
+        ),
+        except_countries: parse_countries(
+            &settings,
+            ARGS_ABUSEIPDB_EXCEPT_COUNTRIES,
+        ),
+        ip_version: abuseipdb_ip_version,
     };
-    let db_path = match matches.value_of(ARGS_DATABASE_PATH) {
-        Some(v) => v.into(),
-        None => {
-            error!("Database Path is required");
+    if !abuseipdb_query.only_countries.is_empty()
+        && !abuseipdb_query.except_countries.is_empty()
+    {
+        error!(
+            "Only one of {} and {} may be given",
+            ARGS_ABUSEIPDB_ONLY_COUNTRIES,
+            ARGS_ABUSEIPDB_EXCEPT_COUNTRIES
+        );
+        exit(1);
+    }
+    let mut provider_schedules = HashMap::new();
+    if let Some(spec) = settings
+        .file
+        .abuseipdb
+        .as_ref()
+        .and_then(|abuseipdb| abuseipdb.schedule.as_ref())
+    {
+        provider_schedules
+            .insert(BackendType::AbuseIpDb, parse_schedule(spec));
+    }
+    let url_lists: Vec<UrlList> =
+        if matches.occurrences_of(ARGS_BACKEND_URL_LIST) > 0 {
+            matches
+                .values_of(ARGS_BACKEND_URL_LIST)
+                .map(|values| values.collect())
+                .unwrap_or_else(Vec::new)
+                .into_iter()
+                .map(|spec| match UrlList::parse(spec) {
+                    Ok(v) => v,
+                    Err(err) => {
+                        error!(
+                            "Invalid url list \"{}\": {}",
+                            spec, err
+                        );
+                        exit(1);
+                    }
+                })
+                .collect()
+        } else {
+            settings
+                .file
+                .url_list
+                .iter()
+                .map(|list| {
+                    match UrlList::new(
+                        list.id,
+                        list.url.clone(),
+                        list.name.clone(),
+                        list.comment.clone(),
+                        list.column,
+                    ) {
+                        Ok(v) => {
+                            if let Some(spec) = &list.schedule {
+                                provider_schedules.insert(
+                                    v.backend_type(),
+                                    parse_schedule(spec),
+                                );
+                            }
+                            v
+                        }
+                        Err(err) => {
+                            error!(
+                                "Invalid url list \"{}\": {}",
+                                list.url, err
+                            );
+                            exit(1);
+                        }
+                    }
+                })
+                .collect()
+        };
+    let mut url_list_types = HashSet::new();
+    if let Some(list) = url_lists
+        .iter()
+        .find(|list| !url_list_types.insert(list.backend_type()))
+    {
+        error!("Duplicate url list id {}", list.backend_type());
+        exit(1);
+    }
+    let spamhaus: Vec<Spamhaus> =
+        if matches.occurrences_of(ARGS_BACKEND_SPAMHAUS) > 0 {
+            matches
+                .values_of(ARGS_BACKEND_SPAMHAUS)
+                .map(|values| values.collect())
+                .unwrap_or_else(Vec::new)
+                .into_iter()
+                .map(|spec| match Spamhaus::parse(spec) {
+                    Ok(v) => v,
+                    Err(err) => {
+                        error!(
+                            "Invalid spamhaus list \"{}\": {}",
+                            spec, err
+                        );
+                        exit(1);
+                    }
+                })
+                .collect()
+        } else {
+            settings
+                .file
+                .spamhaus
+                .iter()
+                .map(|list| {
+                    match Spamhaus::new(
+                        &list.list,
+                        list.url.as_deref(),
+                    ) {
+                        Ok(v) => {
+                            if let Some(spec) = &list.schedule {
+                                provider_schedules.insert(
+                                    v.backend_type(),
+                                    parse_schedule(spec),
+                                );
+                            }
+                            v
+                        }
+                        Err(err) => {
+                            error!(
+                            "Invalid spamhaus list \"{}\": {}",
+                            list.list, err
+                        );
+                            exit(1);
+                        }
+                    }
+                })
+                .collect()
+        };
+    if let Command::Serve | Command::Update { .. } = command {
+        if api_abuseipdb.is_none()
+            && url_lists.is_empty()
+            && spamhaus.is_empty()
+        {
+            error!("At least one provider is required");
             exit(1);
         }
-    };
-
-    let api_abuseipdb = matches
-        .value_of(ARGS_BACKEND_ABUSEIPDB)
-        .map(|v| v.into());
+    }
+    let allowlist = settings
+        .file
+        .allowlist
+        .iter()
+        .map(|entry| match parse_network(&entry.ip) {
+            Some((address, length)) => Allowlist::new(
+                address,
+                length,
+                entry.comment.clone(),
+                entry
+                    .created_by
+                    .clone()
+                    .or_else(|| Some(String::from("config"))),
+            ),
+            None => {
+                error!(
+                    "Invalid allowlist entry \"{}\"",
+                    entry.ip
+                );
+                exit(1);
+            }
+        })
+        .collect();
 
     CliArguments {
+        command,
         listen,
         port,
-        log_level,
         expiration_days,
         stale_days,
+        history_days,
+        reserved_filter,
+        anonymous_read,
+        auto_migrate,
+        schedule,
+        schedule_jitter,
+        recheck_budget,
+        provider_schedules,
         db_type,
         db_host,
         db_port,
@@ -155,12 +634,277 @@ pub fn get_arguments() -> CliArguments {
         db_user,
         db_pass,
         db_path,
+        db_url,
+        db_pool_size,
+        db_connection_timeout,
+        db_idle_timeout,
         api_abuseipdb,
+        abuseipdb_query,
+        url_lists,
+        spamhaus,
+        allowlist,
+    }
+}
+
+/// Resolves settings in the order flags, environment variables, config
+/// file and defaults. Environment variables are named after the flag,
+/// e.g. `BLACKLISTD_DB_TYPE` for `--db-type`.
+struct Settings<'a> {
+    matches: &'a ArgMatches<'a>,
+    file: ConfigFile,
+}
+
+impl<'a> Settings<'a> {
+    fn value(&self, arg: &str) -> Option<String> {
+        if self.matches.occurrences_of(arg) > 0 {
+            return self.matches.value_of(arg).map(String::from);
+        }
+        env_value(arg).or_else(|| self.file_value(arg)).or_else(
+            || self.matches.value_of(arg).map(String::from),
+        )
+    }
+
+    /// Parses the value of the given setting. Exits on invalid values.
+    fn parse<T: FromStr>(&self, arg: &str) -> Option<T> {
+        self.value(arg).map(|value| match value.parse() {
+            Ok(v) => v,
+            Err(_) => {
+                error!("Invalid value \"{}\" for {}", value, arg);
+                exit(1);
+            }
+        })
+    }
+
+    fn required(
+        &self,
+        arg: &str,
+        name: &str,
+        required: bool,
+    ) -> String {
+        match self.value(arg) {
+            Some(v) => v,
+            None if !required => String::new(),
+            None => {
+                error!("{} is required", name);
+                exit(1);
+            }
+        }
+    }
+
+    /// Whether the given `--no-*` switch is set. The config file uses
+    /// the positive form, e.g. `reserved-filter = false`.
+    fn disabled(&self, arg: &str, enabled: Option<bool>) -> bool {
+        if self.matches.is_present(arg) {
+            return true;
+        }
+        match env_value(arg) {
+            Some(value) => match &value.to_lowercase()[..] {
+                "1" | "true" | "yes" => true,
+                "0" | "false" | "no" => false,
+                _ => {
+                    error!(
+                        "Invalid value \"{}\" for {}",
+                        value, arg
+                    );
+                    exit(1);
+                }
+            },
+            None => {
+                enabled.map(|enabled| !enabled).unwrap_or(false)
+            }
+        }
+    }
+
+    fn log_level(&self) -> Option<Level> {
+        match self.value(ARGS_LOG_LEVEL) {
+            Some(ref level) if level == "off" => None,
+            Some(level) => match level.parse() {
+                Ok(level) => Some(level),
+                Err(_) => {
+                    eprintln!("Invalid log level \"{}\"", level);
+                    exit(1);
+                }
+            },
+            None => Some(Level::Info),
+        }
+    }
+
+    fn file_value(&self, arg: &str) -> Option<String> {
+        let file = &self.file;
+        let database = &file.database;
+        match arg {
+            ARGS_LISTEN => file.listen.clone(),
+            ARGS_PORT => file.port.map(|v| v.to_string()),
+            ARGS_LOG_LEVEL => file.log_level.clone(),
+            ARGS_EXPIRATION_DAYS => {
+                file.expiration_days.map(|v| v.to_string())
+            }
+            ARGS_STALE_DAYS => {
+                file.stale_days.map(|v| v.to_string())
+            }
+            ARGS_HISTORY_DAYS => {
+                file.history_days.map(|v| v.to_string())
+            }
+            ARGS_SCHEDULE => file.schedule.update.clone(),
+            ARGS_SCHEDULE_JITTER => {
+                file.schedule.jitter.map(|v| v.to_string())
+            }
+            ARGS_RECHECK_BUDGET => file
+                .schedule
+                .recheck_budget
+                .map(|v| v.to_string()),
+            ARGS_DATABASE_TYPE => database.db_type.clone(),
+            ARGS_DATABASE_HOST => database.host.clone(),
+            ARGS_DATABASE_PORT => {
+                database.port.map(|v| v.to_string())
+            }
+            ARGS_DATABASE_NAME => database.name.clone(),
+            ARGS_DATABASE_USER => database.user.clone(),
+            ARGS_DATABASE_PASS => database.pass.clone(),
+            ARGS_DATABASE_PATH => database.path.clone(),
+            ARGS_DATABASE_URL => database.url.clone(),
+            ARGS_DATABASE_POOL_SIZE => {
+                database.pool_size.map(|v| v.to_string())
+            }
+            ARGS_DATABASE_CONNECTION_TIMEOUT => {
+                database.connection_timeout.map(|v| v.to_string())
+            }
+            ARGS_DATABASE_IDLE_TIMEOUT => {
+                database.idle_timeout.map(|v| v.to_string())
+            }
+            ARGS_BACKEND_ABUSEIPDB => file
+                .abuseipdb
+                .as_ref()
+                .map(|abuseipdb| abuseipdb.api_key.clone()),
+            ARGS_ABUSEIPDB_CONFIDENCE => file
+                .abuseipdb
+                .as_ref()
+                .and_then(|abuseipdb| abuseipdb.confidence)
+                .map(|v| v.to_string()),
+            ARGS_ABUSEIPDB_LIMIT => file
+                .abuseipdb
+                .as_ref()
+                .and_then(|abuseipdb| abuseipdb.limit)
+                .map(|v| v.to_string()),
+            ARGS_ABUSEIPDB_ONLY_COUNTRIES => file
+                .abuseipdb
+                .as_ref()
+                .map(|abuseipdb| {
+                    abuseipdb.only_countries.join(",")
+                })
+                .filter(|v| !v.is_empty()),
+            ARGS_ABUSEIPDB_EXCEPT_COUNTRIES => file
+                .abuseipdb
+                .as_ref()
+                .map(|abuseipdb| {
+                    abuseipdb.except_countries.join(",")
+                })
+                .filter(|v| !v.is_empty()),
+            ARGS_ABUSEIPDB_IP_VERSION => file
+                .abuseipdb
+                .as_ref()
+                .and_then(|abuseipdb| abuseipdb.ip_version)
+                .map(|v| v.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the `BLACKLISTD_*` environment variable of the given setting.
+/// Empty variables are ignored.
+fn env_value(arg: &str) -> Option<String> {
+    let name = format!(
+        "{}{}",
+        ENV_PREFIX,
+        arg.to_uppercase().replace('-', "_")
+    );
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// Parses a comma separated list of ISO 3166 alpha-2 country codes.
+fn parse_countries(
+    settings: &Settings,
+    arg: &str,
+) -> Vec<String> {
+    settings
+        .value(arg)
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|code| !code.is_empty())
+                .map(|code| {
+                    if code.len() != 2
+                        || !code
+                            .chars()
+                            .all(|c| c.is_ascii_alphabetic())
+                    {
+                        error!(
+                            "Invalid country code \"{}\" for {}",
+                            code, arg
+                        );
+                        exit(1);
+                    }
+                    code.to_ascii_uppercase()
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn parse_schedule(spec: &str) -> Schedule {
+    match Schedule::parse(spec) {
+        Ok(schedule) => schedule,
+        Err(err) => {
+            error!("Invalid schedule \"{}\": {}", spec, err);
+            exit(1);
+        }
+    }
+}
+
+fn get_token_command(matches: &ArgMatches) -> TokenCommand {
+    match matches.subcommand() {
+        (CMD_TOKEN_CREATE, Some(matches)) => {
+            let name = matches
+                .value_of(ARGS_TOKEN_NAME)
+                .unwrap_or_default()
+                .into();
+            let scope = match matches
+                .value_of(ARGS_TOKEN_SCOPE)
+                .and_then(TokenScope::parse)
+            {
+                Some(v) => v,
+                None => {
+                    error!("Token Scope is required");
+                    exit(1);
+                }
+            };
+            TokenCommand::Create { name, scope }
+        }
+        (CMD_TOKEN_REVOKE, Some(matches)) => {
+            let name = matches
+                .value_of(ARGS_TOKEN_NAME)
+                .unwrap_or_default()
+                .into();
+            TokenCommand::Revoke { name }
+        }
+        _ => TokenCommand::List,
     }
 }
 
 fn get_cli_config<'a>() -> ArgMatches<'a> {
     app_from_crate!()
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .after_help("Every option can also be set using an environment variable named after it, e.g. BLACKLISTD_DB_TYPE for --db-type, or in the config file. Flags take precedence over environment variables, which take precedence over the config file.")
+        .arg(
+            Arg::with_name(ARGS_CONFIG)
+                .short("c")
+                .long(ARGS_CONFIG)
+                .value_name("path")
+                .env(ARGS_CONFIG_ENV)
+                .help("TOML config file")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name(ARGS_LISTEN)
                 .short("l")
@@ -195,6 +939,15 @@ fn get_cli_config<'a>() -> ArgMatches<'a> {
                 .multiple(true)
                 .help("Decrease verbosity. Once for warning, twice for error, thrice for none")
         )
+        .arg(
+            Arg::with_name(ARGS_LOG_LEVEL)
+                .long(ARGS_LOG_LEVEL)
+                .value_name("level")
+                .possible_values(&["trace", "debug", "info", "warn", "error", "off"])
+                .conflicts_with_all(&[ARGS_VERBOSE, ARGS_SILENT])
+                .help("Set the log level")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name(ARGS_EXPIRATION_DAYS)
                 .long(ARGS_EXPIRATION_DAYS)
@@ -211,14 +964,60 @@ fn get_cli_config<'a>() -> ArgMatches<'a> {
                 .default_value(ARGS_STALE_DAYS_DEFAULT)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name(ARGS_HISTORY_DAYS)
+                .long(ARGS_HISTORY_DAYS)
+                .value_name("days")
+                .help("Days until history records are removed")
+                .default_value(ARGS_HISTORY_DAYS_DEFAULT)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARGS_NO_RESERVED_FILTER)
+                .long(ARGS_NO_RESERVED_FILTER)
+                .help("Store private, loopback, multicast and other reserved ranges returned by providers")
+        )
+        .arg(
+            Arg::with_name(ARGS_NO_ANONYMOUS_READ)
+                .long(ARGS_NO_ANONYMOUS_READ)
+                .help("Require a token with read scope to access the blacklist and stats")
+        )
+        .arg(
+            Arg::with_name(ARGS_NO_MIGRATE)
+                .long(ARGS_NO_MIGRATE)
+                .help("Do not apply pending database migrations on startup. Startup fails if the schema is behind")
+        )
+        .arg(
+            Arg::with_name(ARGS_SCHEDULE)
+                .long(ARGS_SCHEDULE)
+                .value_name("schedule")
+                .help("Schedule of the blacklist updates. Either a cron expression like \"0 */6 * * *\" (UTC, weekdays are numbered 1 = Sunday to 7 = Saturday, names like Mon-Fri are allowed) or an interval like \"every 6h\"")
+                .default_value(ARGS_SCHEDULE_DEFAULT)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARGS_SCHEDULE_JITTER)
+                .long(ARGS_SCHEDULE_JITTER)
+                .value_name("seconds")
+                .help("Delays every update by a random amount of up to the given seconds")
+                .default_value(ARGS_SCHEDULE_JITTER_DEFAULT)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARGS_RECHECK_BUDGET)
+                .long(ARGS_RECHECK_BUDGET)
+                .value_name("checks")
+                .help("Checks per day used to recheck old entries while the provider did not report its remaining budget yet")
+                .default_value(ARGS_RECHECK_BUDGET_DEFAULT)
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name(ARGS_DATABASE_TYPE)
                 .long(ARGS_DATABASE_TYPE)
                 .value_name("type")
                 .possible_values(&["postgres", "mysql", "sqlite"])
                 .help("Database Type")
-                .takes_value(true)
-                .required(true),
+                .takes_value(true),
         )
         .arg(
             Arg::with_name(ARGS_DATABASE_HOST)
@@ -226,15 +1025,6 @@ fn get_cli_config<'a>() -> ArgMatches<'a> {
                 .value_name("hostname/ip")
                 .help("Database Hostname or Ip")
                 .takes_value(true)
-                .required_ifs(&[
-                    (ARGS_DATABASE_TYPE, "postgres"),
-                    (ARGS_DATABASE_TYPE, "mysql"),
-                ])
-                .default_value_if(
-                    ARGS_DATABASE_TYPE,
-                    Some("sqlite"),
-                    "",
-                ),
         )
         .arg(
             Arg::with_name(ARGS_DATABASE_PORT)
@@ -242,15 +1032,6 @@ fn get_cli_config<'a>() -> ArgMatches<'a> {
                 .value_name("port")
                 .help("Database Port")
                 .takes_value(true)
-                .required_ifs(&[
-                    (ARGS_DATABASE_TYPE, "postgres"),
-                    (ARGS_DATABASE_TYPE, "mysql"),
-                ])
-                .default_value_if(
-                    ARGS_DATABASE_TYPE,
-                    Some("sqlite"),
-                    "0",
-                ),
         )
         .arg(
             Arg::with_name(ARGS_DATABASE_NAME)
@@ -258,15 +1039,6 @@ fn get_cli_config<'a>() -> ArgMatches<'a> {
                 .value_name("name")
                 .help("Database Name")
                 .takes_value(true)
-                .required_ifs(&[
-                    (ARGS_DATABASE_TYPE, "postgres"),
-                    (ARGS_DATABASE_TYPE, "mysql"),
-                ])
-                .default_value_if(
-                    ARGS_DATABASE_TYPE,
-                    Some("sqlite"),
-                    "",
-                ),
         )
         .arg(
             Arg::with_name(ARGS_DATABASE_USER)
@@ -274,15 +1046,6 @@ fn get_cli_config<'a>() -> ArgMatches<'a> {
                 .value_name("username")
                 .help("Database Username")
                 .takes_value(true)
-                .required_ifs(&[
-                    (ARGS_DATABASE_TYPE, "postgres"),
-                    (ARGS_DATABASE_TYPE, "mysql"),
-                ])
-                .default_value_if(
-                    ARGS_DATABASE_TYPE,
-                    Some("sqlite"),
-                    "",
-                ),
         )
         .arg(
             Arg::with_name(ARGS_DATABASE_PASS)
@@ -290,15 +1053,6 @@ fn get_cli_config<'a>() -> ArgMatches<'a> {
                 .value_name("password")
                 .help("Database Password")
                 .takes_value(true)
-                .required_ifs(&[
-                    (ARGS_DATABASE_TYPE, "postgres"),
-                    (ARGS_DATABASE_TYPE, "mysql"),
-                ])
-                .default_value_if(
-                    ARGS_DATABASE_TYPE,
-                    Some("sqlite"),
-                    "",
-                ),
         )
         .arg(
             Arg::with_name(ARGS_DATABASE_PATH)
@@ -306,20 +1060,210 @@ fn get_cli_config<'a>() -> ArgMatches<'a> {
                 .value_name("path")
                 .help("Database Path")
                 .takes_value(true)
-                .required_ifs(&[(ARGS_DATABASE_TYPE, "sqlite")])
-                .default_value_ifs(&[
-                    (ARGS_DATABASE_TYPE, Some("postgres"), ""),
-                    (ARGS_DATABASE_TYPE, Some("mysql"), ""),
-                ]),
+        )
+        .arg(
+            Arg::with_name(ARGS_DATABASE_URL)
+                .long(ARGS_DATABASE_URL)
+                .value_name("url")
+                .help("Database Url or Path for sqlite. Supersedes host, port, name, user, password and path")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARGS_DATABASE_POOL_SIZE)
+                .long(ARGS_DATABASE_POOL_SIZE)
+                .value_name("connections")
+                .help("Maximum number of database connections")
+                .default_value(ARGS_DATABASE_POOL_SIZE_DEFAULT)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARGS_DATABASE_CONNECTION_TIMEOUT)
+                .long(ARGS_DATABASE_CONNECTION_TIMEOUT)
+                .value_name("seconds")
+                .help("Seconds to wait for a database connection")
+                .default_value(ARGS_DATABASE_CONNECTION_TIMEOUT_DEFAULT)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARGS_DATABASE_IDLE_TIMEOUT)
+                .long(ARGS_DATABASE_IDLE_TIMEOUT)
+                .value_name("seconds")
+                .help("Seconds until idle database connections are closed. 0 keeps them open")
+                .default_value(ARGS_DATABASE_IDLE_TIMEOUT_DEFAULT)
+                .takes_value(true),
         )
         .arg(
             Arg::with_name(ARGS_BACKEND_ABUSEIPDB)
                 .long(ARGS_BACKEND_ABUSEIPDB)
                 .value_name("abuseipdb-api")
                 .help("API Key for abuseipdb")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARGS_ABUSEIPDB_CONFIDENCE)
+                .long(ARGS_ABUSEIPDB_CONFIDENCE)
+                .value_name("score")
+                .help("Minimum abuse confidence score (25 - 100) of downloaded and rechecked abuseipdb entries")
+                .default_value(ARGS_ABUSEIPDB_CONFIDENCE_DEFAULT)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARGS_ABUSEIPDB_LIMIT)
+                .long(ARGS_ABUSEIPDB_LIMIT)
+                .value_name("ips")
+                .help("Maximum number of downloaded abuseipdb entries. Paid plans allow more than the default of 10000")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARGS_ABUSEIPDB_ONLY_COUNTRIES)
+                .long(ARGS_ABUSEIPDB_ONLY_COUNTRIES)
+                .value_name("countries")
+                .help("Comma separated country codes, only abuseipdb entries of these countries are downloaded")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARGS_ABUSEIPDB_EXCEPT_COUNTRIES)
+                .long(ARGS_ABUSEIPDB_EXCEPT_COUNTRIES)
+                .value_name("countries")
+                .help("Comma separated country codes, abuseipdb entries of these countries are not downloaded")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARGS_ABUSEIPDB_IP_VERSION)
+                .long(ARGS_ABUSEIPDB_IP_VERSION)
+                .value_name("version")
+                .possible_values(&["4", "6"])
+                .help("Only download abuseipdb entries of the given ip version")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ARGS_BACKEND_URL_LIST)
+                .long(ARGS_BACKEND_URL_LIST)
+                .value_name("id=<id>,url=<url>[,name=<name>][,comment=<prefix>][,column=<index>]")
+                .help("Plain text feed with one ip per line. May be given multiple times")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name(ARGS_BACKEND_SPAMHAUS)
+                .long(ARGS_BACKEND_SPAMHAUS)
+                .value_name("drop|edrop[=<url>]")
+                .help("Spamhaus DROP or EDROP list. May be given multiple times")
                 .takes_value(true)
-                // For future addition of new backends
-                .required_unless_one(&[]),
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .subcommand(
+            SubCommand::with_name(CMD_MIGRATE)
+                .about("Apply pending database migrations")
+                .arg(
+                    Arg::with_name(ARGS_MIGRATE_DRY_RUN)
+                        .long(ARGS_MIGRATE_DRY_RUN)
+                        .help("Only list pending migrations"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(CMD_UPDATE)
+                .about("Update the blacklist once and exit")
+                .arg(
+                    Arg::with_name(ARGS_UPDATE_PROVIDER)
+                        .long(ARGS_UPDATE_PROVIDER)
+                        .value_name("provider")
+                        .help("Only update the given provider, e.g. abuseipdb, spamhaus-drop or feed-100. May be given multiple times")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(CMD_LIST)
+                .about("List every blacklist entry"),
+        )
+        .subcommand(
+            SubCommand::with_name(CMD_ADD)
+                .about("Add an ip or network to the blacklist")
+                .arg(
+                    Arg::with_name(ARGS_NETWORK)
+                        .help("Ip or network in CIDR notation")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name(ARGS_TTL)
+                        .long(ARGS_TTL)
+                        .value_name("seconds")
+                        .help("Removes the entry after the given seconds. Kept forever if missing")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name(ARGS_REASON)
+                        .long(ARGS_REASON)
+                        .value_name("reason")
+                        .help("Reason stored with the entry")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(CMD_REMOVE)
+                .about("Remove an ip or network from the blacklist, regardless of the provider")
+                .arg(
+                    Arg::with_name(ARGS_NETWORK)
+                        .help("Ip or network in CIDR notation")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(CMD_CHECK)
+                .about("Show every entry listing the given ip")
+                .arg(
+                    Arg::with_name(ARGS_ADDRESS)
+                        .help("Ip to look up")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(CMD_STATS)
+                .about("Show the number of entries per provider"),
+        )
+        .subcommand(
+            SubCommand::with_name(CMD_FSCK)
+                .about("Report stored networks which can not be decoded"),
+        )
+        .subcommand(
+            SubCommand::with_name(CMD_TOKEN)
+                .about("Manage api tokens")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name(CMD_TOKEN_CREATE)
+                        .about("Create a token and print it. The token can not be shown again")
+                        .arg(
+                            Arg::with_name(ARGS_TOKEN_NAME)
+                                .help("Unique name of the token")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name(ARGS_TOKEN_SCOPE)
+                                .long(ARGS_TOKEN_SCOPE)
+                                .value_name("scope")
+                                .possible_values(&["read", "write", "admin"])
+                                .help("Permissions of the token. Every scope includes the ones before")
+                                .default_value(ARGS_TOKEN_SCOPE_DEFAULT)
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name(CMD_TOKEN_REVOKE)
+                        .about("Revoke a token")
+                        .arg(
+                            Arg::with_name(ARGS_TOKEN_NAME)
+                                .help("Name of the token")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name(CMD_TOKEN_LIST)
+                        .about("List all tokens"),
+                ),
         )
         .get_matches()
 }
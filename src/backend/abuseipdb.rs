@@ -1,33 +1,34 @@
 use crate::{
-    args::CliArguments,
-    db_op,
-    helper::get_elapsed_time,
-    middleware::diesel::{DBType, DieselPooledConnection},
-    schema::blacklist::dsl::*,
-    types::{backend_type::BackendType, blacklist::Blacklist},
+    backend::provider::{Provider, Recheck},
+    types::{
+        backend_type::BackendType,
+        blacklist::{Blacklist, Reputation},
+        rate_limit::RateLimit,
+        report::Report,
+    },
 };
-use diesel::{
-    BoolExpressionMethods, ExpressionMethods, QueryDsl,
-    RunQueryDsl,
-};
-use log::{debug, error, info, warn};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use log::{error, warn};
 use reqwest::{
-    blocking::Client,
+    blocking::{Client, Response},
     header::{HeaderName, ACCEPT},
+    StatusCode,
 };
-use std::any::Any;
-use time::OffsetDateTime;
+use std::{collections::HashMap, convert::TryFrom, sync::Mutex};
+
+const ENDPOINT_BLACKLIST: &str = "blacklist";
+const ENDPOINT_CHECK: &str = "check";
+const ENDPOINT_REPORT: &str = "report";
 
 macro_rules! abipdb_call {
-    ($client:expr, $url:expr, $accept:expr, $key:expr) => {
+    ($abuseipdb:expr, $request:expr, $accept:expr, $endpoint:expr) => {
         {
-            let response = $client
-                .get($url)
+            let response = $request
                 .header(ACCEPT, $accept)
                 .header(
                     HeaderName::from_lowercase(b"key")
                         .expect("Invalid header"),
-                    $key.api_abuseipdb.as_ref().map(|s| &s[..]).unwrap_or(""),
+                    &$abuseipdb.key,
                 )
                 .send()
                 .map_err(|err| {
@@ -37,6 +38,7 @@ macro_rules! abipdb_call {
                     );
                 })
                 .ok()?;
+            $abuseipdb.update_rate_limit($endpoint, &response);
             let code = response.status();
             let mut response_val = None;
             if code.is_success() {
@@ -49,7 +51,7 @@ macro_rules! abipdb_call {
                             );
                         })
                         .ok();
-            } else if code.is_client_error() {
+            } else if code == StatusCode::TOO_MANY_REQUESTS {
                 warn!(
                     "Hit a Request Limit while fetching abuseipdb: {}",
                     code
@@ -65,285 +67,443 @@ macro_rules! abipdb_call {
     }
 }
 
-pub fn update_abuseipdb(
-    args: &CliArguments,
-    db_conn: &dyn Any,
-    db_type: DBType,
-    client: &Client,
-) {
-    info!("Fetching abuseipdb");
-    let time = OffsetDateTime::now();
-    if let Some(response) =
-        fetch_abuseipdb_blacklist(args, client)
-    {
-        debug!("Storing abuseipdb");
-        store_abuseipdb(db_conn, &response, db_type);
-    }
-    // store_abuseipdb(
-    //     db_conn,
-    //     &include_str!("../blacklist"),
-    //     db_type,
-    // );
-    debug!("Updating old abuseipdb ips");
-    update_old_ips(args, db_conn, db_type, client);
-    info!(
-        "abuseipdb completed after {} s",
-        get_elapsed_time(time)
-    );
+/// Parameters of the blacklist download.
+#[derive(Debug, Clone)]
+pub struct BlacklistQuery {
+    /// Minimum `abuseConfidenceScore` of a listed ip. Also used by
+    /// rechecks.
+    pub confidence: u8,
+    /// Maximum number of ips. Uses the default of AbuseIPDB if missing.
+    pub limit: Option<u32>,
+    pub only_countries: Vec<String>,
+    pub except_countries: Vec<String>,
+    /// Either 4 or 6. Both are returned if missing.
+    pub ip_version: Option<u8>,
 }
 
-#[allow(dead_code)]
-fn fetch_abuseipdb_blacklist(
-    args: &CliArguments,
-    client: &Client,
-) -> Option<String> {
-    abipdb_call!(
-        client,
-        "https://api.abuseipdb.com/api/v2/blacklist",
-        "text/plain",
-        args
-    )
+impl BlacklistQuery {
+    fn to_query(&self) -> String {
+        let mut query =
+            format!("confidenceMinimum={}", self.confidence);
+        if let Some(limit) = self.limit {
+            query.push_str(&format!("&limit={}", limit));
+        }
+        if !self.only_countries.is_empty() {
+            query.push_str(&format!(
+                "&onlyCountries={}",
+                self.only_countries.join(",")
+            ));
+        }
+        if !self.except_countries.is_empty() {
+            query.push_str(&format!(
+                "&exceptCountries={}",
+                self.except_countries.join(",")
+            ));
+        }
+        if let Some(ip_version) = self.ip_version {
+            query.push_str(&format!("&ipVersion={}", ip_version));
+        }
+        query
+    }
 }
 
-fn store_abuseipdb(
-    db_conn: &dyn Any,
-    response: &str,
-    db_type: DBType,
-) {
-    let mut operations = 0;
-    let mut errors = 0;
+/// Reads the score and details of a single ip of a blacklist or check
+/// response. Values out of range are dropped.
+fn parse_reputation(
+    data: &serde_json::Map<String, serde_json::Value>,
+) -> Reputation {
+    let text = |name: &str| {
+        data.get(name).and_then(|v| v.as_str()).map(String::from)
+    };
+    Reputation {
+        score: data
+            .get("abuseConfidenceScore")
+            .and_then(|v| v.as_i64())
+            .and_then(|v| i16::try_from(v).ok()),
+        total_reports: data
+            .get("totalReports")
+            .and_then(|v| v.as_i64())
+            .and_then(|v| i32::try_from(v).ok()),
+        last_reported: text("lastReportedAt")
+            .and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+            .map(|v| v.naive_utc()),
+        country_code: text("countryCode"),
+        usage_type: text("usageType"),
+    }
+}
 
-    for entry in response.lines().filter_map(|line| {
-        Blacklist::new(line, BackendType::AbuseIpDb)
-    }) {
-        operations += 1;
-        match db_type {
-            DBType::POSTGRES => {
-                let conn = db_conn
-                    .downcast_ref::<DieselPooledConnection<
-                        diesel::pg::PgConnection,
-                    >>()
-                    .expect("Downcast failed");
+pub struct AbuseIpDb {
+    key: String,
+    query: BlacklistQuery,
+    /// Request budget of every endpoint, see `update_rate_limit`.
+    rate_limits: Mutex<HashMap<String, RateLimit>>,
+}
 
-                let _ = diesel::insert_into(blacklist)
-                    .values(&entry)
-                    .on_conflict((ip, ip_type))
-                    .do_update()
-                    .set(&entry)
-                    .execute(conn)
-                    .map_err(|err| {
-                        errors += 1;
-                        error!(
-                            "Unable to update ip: {} with: {:#?}",
-                            entry
-                                .to_plain()
-                                .unwrap_or_else(String::new),
-                            err
-                        )
-                    });
-            }
-            DBType::MYSQL => {
-                let conn = db_conn
-                    .downcast_ref::<DieselPooledConnection<
-                        diesel::mysql::MysqlConnection,
-                    >>()
-                    .expect("Downcast failed");
+impl AbuseIpDb {
+    pub fn new(key: &str, query: BlacklistQuery) -> Self {
+        AbuseIpDb {
+            key: key.into(),
+            query,
+            rate_limits: Mutex::new(HashMap::new()),
+        }
+    }
 
-                let _ = diesel::replace_into(blacklist)
-                    .values(&entry)
-                    .execute(conn)
-                    .map_err(|err| {
-                        errors += 1;
-                        error!(
-                            "Unable to update ip: {} with: {:#?}",
-                            entry
-                                .to_plain()
-                                .unwrap_or_else(String::new),
-                            err
-                        )
-                    });
-            }
-            DBType::SQLITE => {
-                let conn = db_conn
-                    .downcast_ref::<DieselPooledConnection<
-                        diesel::sqlite::SqliteConnection,
-                    >>()
-                    .expect("Downcast failed");
+    /// The time the budget of the given endpoint is available again,
+    /// if it is exhausted.
+    fn exhausted_until(
+        &self,
+        endpoint: &str,
+    ) -> Option<NaiveDateTime> {
+        let now = Utc::now().naive_utc();
+        self.rate_limits
+            .lock()
+            .expect("Poisoned lock")
+            .get(endpoint)
+            .filter(|limit| limit.exhausted(now))
+            .map(|limit| {
+                limit.reset.unwrap_or_else(|| {
+                    limit.updated + Duration::days(1)
+                })
+            })
+    }
 
-                let _ = diesel::replace_into(blacklist)
-                    .values(&entry)
-                    .execute(conn)
-                    .map_err(|err| {
-                        errors += 1;
-                        error!(
-                            "Unable to update ip: {} with: {:#?}",
-                            entry
-                                .to_plain()
-                                .unwrap_or_else(String::new),
-                            err
-                        )
-                    });
+    /// Reads the `X-RateLimit-*` headers of a response. A
+    /// `429 Too Many Requests` response exhausts the budget until the
+    /// time given by `Retry-After`.
+    fn update_rate_limit(
+        &self,
+        endpoint: &str,
+        response: &Response,
+    ) {
+        let header = |name: &str| -> Option<i64> {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok())
+        };
+        let now = Utc::now().naive_utc();
+        let mut rate_limits =
+            self.rate_limits.lock().expect("Poisoned lock");
+        let limit = rate_limits
+            .entry(endpoint.into())
+            .or_insert_with(|| {
+                RateLimit::new(BackendType::AbuseIpDb, endpoint)
+            });
+        limit.updated = now;
+        if let Some(value) = header("x-ratelimit-limit") {
+            limit.request_limit = Some(value as i32);
+        }
+        if let Some(value) = header("x-ratelimit-remaining") {
+            limit.remaining = Some(value as i32);
+        }
+        if let Some(value) = header("x-ratelimit-reset") {
+            limit.reset = DateTime::from_timestamp(value, 0)
+                .map(|reset| reset.naive_utc());
+        }
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            limit.remaining = Some(0);
+            if let Some(value) = header("retry-after") {
+                limit.reset =
+                    Some(now + Duration::seconds(value));
             }
         }
     }
-    debug!(
-        "Store Completed. {} Inserts or Updates, {} Errors",
-        operations - errors,
-        errors
-    );
+
+    fn fetch_blacklist(&self, client: &Client) -> Option<String> {
+        abipdb_call!(
+            self,
+            client.get(&format!(
+                "https://api.abuseipdb.com/api/v2/blacklist?{}",
+                self.query.to_query()
+            )),
+            "application/json",
+            ENDPOINT_BLACKLIST
+        )
+    }
+
+    fn fetch_ip(
+        &self,
+        client: &Client,
+        address: &str,
+    ) -> Option<Recheck> {
+        let response: String = abipdb_call!(
+            self,
+            client.get(&format!(
+                "https://api.abuseipdb.com/api/v2/check?ipAddress={}",
+                address
+            )),
+            "application/json",
+            ENDPOINT_CHECK
+        )?;
+
+        serde_json::from_str(&response)
+            .map_err(|err| {
+                error!(
+                    "Unable to parse check json request from abuseipdb: {}",
+                    err
+                );
+            })
+            .ok()
+            .and_then(|v: serde_json::Value| {
+                let reputation = parse_reputation(
+                    v.as_object()?.get("data")?.as_object()?,
+                );
+                Some(Recheck {
+                    listed: reputation.score?
+                        >= i16::from(self.query.confidence),
+                    reputation,
+                })
+            })
+    }
 }
 
-fn update_old_ips(
-    args: &CliArguments,
-    db_conn: &dyn Any,
-    db_type: DBType,
-    client: &Client,
-) {
-    let mut updated = 0;
-    let mut deleted = 0;
-    let mut update_error = 0;
-    let mut delete_error = 0;
+impl AbuseIpDb {
+    /// The time reports can be sent again, if the report budget is
+    /// exhausted.
+    pub fn reports_exhausted_until(
+        &self,
+    ) -> Option<NaiveDateTime> {
+        self.exhausted_until(ENDPOINT_REPORT)
+    }
 
-    let update_threshold = chrono::Utc::now()
-        .checked_sub_signed(chrono::Duration::days(i64::from(
-            args.expiration_days,
-        )))
-        .expect("Unable to substract days")
-        .naive_utc();
-    let filter = blacklist
-        .filter(
-            last_update
-                .lt(update_threshold)
-                .and(backend_type.eq(BackendType::AbuseIpDb)),
+    /// Reports a single ip. Returns `None` if the report was not
+    /// accepted.
+    pub fn report(
+        &self,
+        client: &Client,
+        report: &Report,
+    ) -> Option<()> {
+        let categories = report
+            .categories
+            .iter()
+            .map(u8::to_string)
+            .collect::<Vec<String>>()
+            .join(",");
+        let mut form = vec![
+            ("ip", report.ip.to_string()),
+            ("categories", categories),
+        ];
+        if let Some(comment) = &report.comment {
+            form.push(("comment", comment.clone()));
+        }
+        abipdb_call!(
+            self,
+            client
+                .post("https://api.abuseipdb.com/api/v2/report")
+                .form(&form),
+            "application/json",
+            ENDPOINT_REPORT
         )
-        .order_by(last_update.desc());
-
-    db_op!(db_conn, db_type, conn {
-        update_old_ip(
-            args,
-            || filter.load::<Blacklist>(conn),
-            |entry| {
-                updated += 1;
-                diesel::update(blacklist)
-                    .set(entry)
-                    .filter(ip.eq(&entry.ip)
-                            .and(ip_type.eq(entry.ip_type)))
-                    .execute(conn)
-            },
-            |entry| {
-                deleted += 1;
-                diesel::delete(
-                    blacklist.filter(
-                        ip.eq(&entry.ip)
-                            .and(ip_type.eq(entry.ip_type)),
-                    ),
-                )
-                .execute(conn)
-            },
-            || update_error += 1,
-            || delete_error += 1,
-            client,
-        );
-    });
-    debug!(
-        "Update Completed. {} Updates, {} Deleted, {} Errors",
-        updated - update_error,
-        deleted - delete_error,
-        update_error + delete_error,
-    );
+        .map(|_| ())
+    }
 }
 
-fn update_old_ip<Q, E1, E2, U, D>(
-    args: &CliArguments,
-    query: Q,
-    mut update: U,
-    mut delete: D,
-    mut error_update: E1,
-    mut error_delete: E2,
-    client: &Client,
-) where
-    Q: FnOnce() -> Result<Vec<Blacklist>, diesel::result::Error>,
-    U: FnMut(&Blacklist) -> Result<usize, diesel::result::Error>,
-    D: FnMut(&Blacklist) -> Result<usize, diesel::result::Error>,
-    E1: FnMut() -> (),
-    E2: FnMut() -> (),
-{
-    let values = query().unwrap_or_else(|err| {
-        error!("Unable to load ips with: {:#?}", err);
-        Vec::new()
-    });
+impl Provider for AbuseIpDb {
+    fn name(&self) -> &str {
+        "abuseipdb"
+    }
 
-    for mut value in values {
-        match value.to_plain().and_then(|address| {
-            debug!("Checking ip: {}", address);
-            fetch_abuseipdb_ip(args, client, &address)
-        }) {
-            Some(true) => {
-                value.last_update =
-                    chrono::Utc::now().naive_utc();
-                let _ = update(&value).map_err(|err| {
-                    error_update();
-                    error!(
-                        "Unable to update ip: {} with: {:#?}",
-                        value
-                            .to_plain()
-                            .unwrap_or_else(String::new),
-                        err
-                    )
-                });
-            }
-            Some(false) => {
-                let _ = delete(&value).map_err(|err| {
-                    error_delete();
+    fn backend_type(&self) -> BackendType {
+        BackendType::AbuseIpDb
+    }
+
+    fn fetch(&self, client: &Client) -> Option<Vec<Blacklist>> {
+        if let Some(reset) =
+            self.exhausted_until(ENDPOINT_BLACKLIST)
+        {
+            warn!(
+                "Skipping abuseipdb, blacklist requests exhausted until {}",
+                reset
+            );
+            return None;
+        }
+        let response = self.fetch_blacklist(client)?;
+        let response: serde_json::Value =
+            serde_json::from_str(&response)
+                .map_err(|err| {
                     error!(
-                        "Unable to delete ip: {} with: {:#?}",
-                        value
-                            .to_plain()
-                            .unwrap_or_else(String::new),
+                        "Unable to parse blacklist json request from abuseipdb: {}",
                         err
-                    )
-                });
-            }
+                    );
+                })
+                .ok()?;
+        let data = response
+            .as_object()
+            .and_then(|v| v.get("data"))
+            .and_then(|v| v.as_array());
+        let data = match data {
+            Some(data) => data,
             None => {
-                warn!(
-                    "Unable to fetch value for {}",
-                    value.to_plain().unwrap_or_else(String::new)
-                );
-                return;
+                error!("Missing data in blacklist response from abuseipdb");
+                return None;
             }
+        };
+        Some(
+            data.iter()
+                .filter_map(|v| v.as_object())
+                .filter_map(|v| {
+                    let mut entry = Blacklist::new(
+                        v.get("ipAddress")?.as_str()?,
+                        BackendType::AbuseIpDb,
+                    )?;
+                    let reputation = parse_reputation(v);
+                    entry.score = reputation.score;
+                    entry.last_reported =
+                        reputation.last_reported;
+                    entry.country_code = reputation.country_code;
+                    Some(entry)
+                })
+                .collect(),
+        )
+    }
+
+    fn supports_check(&self) -> bool {
+        true
+    }
+
+    fn check(
+        &self,
+        client: &Client,
+        address: &str,
+    ) -> Option<Recheck> {
+        if let Some(reset) = self.exhausted_until(ENDPOINT_CHECK)
+        {
+            warn!(
+                "Stopping abuseipdb rechecks, check requests exhausted until {}",
+                reset
+            );
+            return None;
+        }
+        self.fetch_ip(client, address)
+    }
+
+    fn rate_limits(&self) -> Vec<RateLimit> {
+        self.rate_limits
+            .lock()
+            .expect("Poisoned lock")
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    fn check_budget(&self) -> Option<RateLimit> {
+        self.rate_limits
+            .lock()
+            .expect("Poisoned lock")
+            .get(ENDPOINT_CHECK)
+            .cloned()
+    }
+
+    fn restore_rate_limits(&self, limits: Vec<RateLimit>) {
+        let mut rate_limits =
+            self.rate_limits.lock().expect("Poisoned lock");
+        for limit in limits {
+            rate_limits.insert(limit.endpoint.clone(), limit);
         }
     }
 }
 
-fn fetch_abuseipdb_ip(
-    args: &CliArguments,
-    client: &Client,
-    address: &str,
-) -> Option<bool> {
-    let response: String = abipdb_call!(
-        client,
-        &format!(
-            "https://api.abuseipdb.com/api/v2/check?ipAddress={}",
-            address
-        ),
-        "application/json",
-        args
-    )?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use serde_json::{json, Map, Value};
 
-    serde_json::from_str(&response)
-        .map_err(|err| {
-            error!(
-                "Unable to parse check json request from abuseipdb: {}",
-                err
-            );
-        })
-        .ok()
-        .and_then(|v: serde_json::Value| {
-            v.as_object()
-                .and_then(|v| v.get("data"))
-                .and_then(|v| v.as_object())
-                .and_then(|v| v.get("abuseConfidenceScore"))
-                .and_then(|v| v.as_i64())
-                .map(|v| v >= 100)
-        })
+    fn query() -> BlacklistQuery {
+        BlacklistQuery {
+            confidence: 90,
+            limit: None,
+            only_countries: Vec::new(),
+            except_countries: Vec::new(),
+            ip_version: None,
+        }
+    }
+
+    fn data(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(data) => data,
+            _ => panic!("Not an object"),
+        }
+    }
+
+    #[test]
+    fn to_query_only_confidence() {
+        assert_eq!(query().to_query(), "confidenceMinimum=90");
+    }
+
+    #[test]
+    fn to_query_every_parameter() {
+        let query = BlacklistQuery {
+            confidence: 75,
+            limit: Some(10000),
+            only_countries: vec![
+                String::from("US"),
+                String::from("CN"),
+            ],
+            except_countries: vec![String::from("DE")],
+            ip_version: Some(6),
+        };
+        assert_eq!(
+            query.to_query(),
+            "confidenceMinimum=75&limit=10000&onlyCountries=US,CN&exceptCountries=DE&ipVersion=6"
+        );
+    }
+
+    #[test]
+    fn parse_reputation_reads_details() {
+        let reputation = parse_reputation(&data(json!({
+            "ipAddress": "192.0.2.1",
+            "abuseConfidenceScore": 100,
+            "totalReports": 42,
+            "lastReportedAt": "2026-10-17T12:00:00+02:00",
+            "countryCode": "US",
+            "usageType": "Data Center/Web Hosting/Transit",
+        })));
+        assert_eq!(reputation.score, Some(100));
+        assert_eq!(reputation.total_reports, Some(42));
+        assert_eq!(
+            reputation.last_reported,
+            NaiveDate::from_ymd_opt(2026, 10, 17)
+                .and_then(|date| date.and_hms_opt(10, 0, 0))
+        );
+        assert_eq!(
+            reputation.country_code.as_deref(),
+            Some("US")
+        );
+        assert_eq!(
+            reputation.usage_type.as_deref(),
+            Some("Data Center/Web Hosting/Transit")
+        );
+    }
+
+    #[test]
+    fn parse_reputation_skips_missing_and_invalid() {
+        let reputation = parse_reputation(&data(json!({
+            "abuseConfidenceScore": "100",
+            "totalReports": null,
+            "lastReportedAt": "yesterday",
+        })));
+        assert_eq!(reputation.score, None);
+        assert_eq!(reputation.total_reports, None);
+        assert_eq!(reputation.last_reported, None);
+        assert_eq!(reputation.country_code, None);
+        assert_eq!(reputation.usage_type, None);
+    }
+
+    #[test]
+    fn parse_reputation_drops_out_of_range() {
+        let reputation = parse_reputation(&data(json!({
+            "abuseConfidenceScore": 40000,
+            "totalReports": 3000000000i64,
+        })));
+        assert_eq!(reputation.score, None);
+        assert_eq!(reputation.total_reports, None);
+        let reputation = parse_reputation(&data(json!({
+            "abuseConfidenceScore": i16::MAX,
+            "totalReports": i32::MAX,
+        })));
+        assert_eq!(reputation.score, Some(i16::MAX));
+        assert_eq!(reputation.total_reports, Some(i32::MAX));
+    }
 }
@@ -0,0 +1,128 @@
+use crate::{
+    args::CliArguments,
+    backend::abuseipdb::AbuseIpDb,
+    types::{
+        backend_type::BackendType,
+        blacklist::{Blacklist, Reputation},
+        rate_limit::RateLimit,
+    },
+};
+use log::error;
+use reqwest::blocking::Client;
+
+/// Result of rechecking a single address.
+#[derive(Debug, Clone)]
+pub struct Recheck {
+    /// Whether the address is still listed.
+    pub listed: bool,
+    /// Score and details reported by the provider, if any.
+    pub reputation: Reputation,
+}
+
+/// A source of blacklist entries.
+///
+/// Every provider is identified by its own `BackendType`, which is
+/// stored alongside the entries it fetched. This allows rechecks and
+/// statistics to be scoped to a single provider.
+pub trait Provider {
+    /// Human readable name used for logging.
+    fn name(&self) -> &str;
+
+    /// Identifier stored with every entry of this provider.
+    fn backend_type(&self) -> BackendType;
+
+    /// Fetches the full list offered by the provider. Returns `None`
+    /// if the list could not be retrieved.
+    fn fetch(&self, client: &Client) -> Option<Vec<Blacklist>>;
+
+    /// Whether the provider is able to recheck single entries.
+    fn supports_check(&self) -> bool {
+        false
+    }
+
+    /// Rechecks a single address. Listed entries are kept, others
+    /// are removed. `None` aborts the recheck run, e.g. because a
+    /// request limit was hit.
+    fn check(
+        &self,
+        _client: &Client,
+        _address: &str,
+    ) -> Option<Recheck> {
+        None
+    }
+
+    /// Request budgets reported by the provider. They are persisted
+    /// after every update and restored before the next one.
+    fn rate_limits(&self) -> Vec<RateLimit> {
+        Vec::new()
+    }
+
+    /// Restores the budgets persisted by a previous update.
+    fn restore_rate_limits(&self, _limits: Vec<RateLimit>) {}
+
+    /// Budget of the requests used by `check`, if it is known.
+    fn check_budget(&self) -> Option<RateLimit> {
+        None
+    }
+}
+
+/// Returns every provider configured by the given arguments.
+pub fn providers(args: &CliArguments) -> Vec<Box<dyn Provider>> {
+    let mut providers: Vec<Box<dyn Provider>> = Vec::new();
+    if let Some(key) = &args.api_abuseipdb {
+        providers.push(Box::new(AbuseIpDb::new(
+            key,
+            args.abuseipdb_query.clone(),
+        )));
+    }
+    for list in &args.spamhaus {
+        providers.push(Box::new(list.clone()));
+    }
+    for list in &args.url_lists {
+        providers.push(Box::new(list.clone()));
+    }
+    providers
+}
+
+/// Fetches the body of the given url. `file://` urls are read from
+/// the local filesystem which allows feeds to be mirrored locally.
+pub fn fetch_text(
+    client: &Client,
+    name: &str,
+    url: &str,
+) -> Option<String> {
+    if let Some(path) = url.strip_prefix("file://") {
+        return std::fs::read_to_string(path)
+            .map_err(|err| {
+                error!(
+                    "Unable to read {} from {}: {}",
+                    name, url, err
+                )
+            })
+            .ok();
+    }
+    let response = client
+        .get(url)
+        .send()
+        .map_err(|err| {
+            error!("Unable to send request to {}: {}", name, err);
+        })
+        .ok()?;
+    let code = response.status();
+    if !code.is_success() {
+        error!(
+            "Hit a Request Error while fetching {}: {}",
+            name, code
+        );
+        return None;
+    }
+    response
+        .text()
+        .map_err(|err| {
+            error!(
+                "Unable to parse response from {}: {}",
+                name, err
+            );
+        })
+        .ok()
+}
@@ -0,0 +1,65 @@
+use crate::{
+    helper::{convert_to_raw, networks_overlap, parse_network},
+    types::{blacklist::Blacklist, ip_type::IpType},
+};
+
+/// Networks which are not routed on the public internet, see
+/// RFC 6890 and the IANA special-purpose address registries.
+const RESERVED_NETWORKS: &[&str] = &[
+    // IPv4
+    "0.0.0.0/8",
+    "10.0.0.0/8",
+    "100.64.0.0/10",
+    "127.0.0.0/8",
+    "169.254.0.0/16",
+    "172.16.0.0/12",
+    "192.0.0.0/24",
+    "192.0.2.0/24",
+    "192.88.99.0/24",
+    "192.168.0.0/16",
+    "198.18.0.0/15",
+    "198.51.100.0/24",
+    "203.0.113.0/24",
+    "224.0.0.0/4",
+    "240.0.0.0/4",
+    // IPv6
+    "::/128",
+    "::1/128",
+    "::ffff:0:0/96",
+    "64:ff9b:1::/48",
+    "100::/64",
+    "2001:db8::/32",
+    "3fff::/20",
+    "fc00::/7",
+    "fe80::/10",
+    "fec0::/10",
+    "ff00::/8",
+];
+
+pub struct ReservedNetworks(Vec<(Vec<u8>, IpType, i16)>);
+
+impl ReservedNetworks {
+    pub fn new() -> Self {
+        ReservedNetworks(
+            RESERVED_NETWORKS
+                .iter()
+                .filter_map(|network| parse_network(network))
+                .map(|(address, prefix)| {
+                    let (raw_ip, ip_type) =
+                        convert_to_raw(address, prefix);
+                    (raw_ip, ip_type, i16::from(prefix))
+                })
+                .collect(),
+        )
+    }
+
+    /// Whether the given entry covers at least one reserved address.
+    pub fn contains(&self, entry: &Blacklist) -> bool {
+        self.0.iter().any(|(raw_ip, ip_type, prefix)| {
+            networks_overlap(
+                (raw_ip, *ip_type, *prefix),
+                (&entry.ip, entry.ip_type, entry.prefix),
+            )
+        })
+    }
+}
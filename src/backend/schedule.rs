@@ -0,0 +1,522 @@
+use crate::{
+    backend::store::UpdateSummary,
+    types::{
+        backend_type::BackendType,
+        report::Report,
+        stats::{NextUpdate, UpdateRun, UpdateStatus},
+    },
+};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use rand::Rng;
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+/// AbuseIPDB accepts a single report of an ip every 15 minutes.
+const REPORT_WINDOW_MINUTES: i64 = 15;
+
+/// When a provider is updated.
+///
+/// Either a cron expression with five fields (`minute hour day month
+/// weekday`), six fields starting with seconds, or an interval like
+/// `every 30m`. Intervals support the units `s`, `m`, `h` and `d`.
+///
+/// Cron expressions are evaluated by the `cron` crate, which numbers
+/// weekdays from 1 (Sunday) to 7 (Saturday) unlike the usual 0 - 6.
+/// Names like `Mon-Fri` avoid the difference.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    Cron(Box<cron::Schedule>),
+    Interval(Duration),
+}
+
+impl Schedule {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        if let Some(interval) = spec.strip_prefix("every ") {
+            let interval = interval.trim();
+            if interval.is_empty() {
+                return Err(String::from("Missing interval"));
+            }
+            let unit_start = interval
+                .char_indices()
+                .last()
+                .map(|(index, _)| index)
+                .unwrap_or(0);
+            let (value, unit) = interval.split_at(unit_start);
+            let value: i64 = value
+                .trim()
+                .parse()
+                .ok()
+                .filter(|value| *value > 0)
+                .ok_or_else(|| {
+                    format!("Invalid interval: {}", interval)
+                })?;
+            let interval = match unit {
+                "s" => Duration::seconds(value),
+                "m" => Duration::minutes(value),
+                "h" => Duration::hours(value),
+                "d" => Duration::days(value),
+                _ => {
+                    return Err(format!("Unknown unit: {}", unit))
+                }
+            };
+            return Ok(Schedule::Interval(interval));
+        }
+        let expression = match spec.split_whitespace().count() {
+            5 => format!("0 {}", spec),
+            _ => spec.to_string(),
+        };
+        cron::Schedule::from_str(&expression)
+            .map(|schedule| Schedule::Cron(Box::new(schedule)))
+            .map_err(|err| {
+                format!("Invalid cron expression: {}", err)
+            })
+    }
+
+    /// The first planned run after the given time.
+    pub fn next_after(
+        &self,
+        time: DateTime<Utc>,
+    ) -> DateTime<Utc> {
+        match self {
+            Schedule::Cron(schedule) => schedule
+                .after(&time)
+                .next()
+                .unwrap_or_else(|| time + Duration::days(1)),
+            Schedule::Interval(interval) => time + *interval,
+        }
+    }
+
+    /// The first planned run after the given time, delayed by up to
+    /// `jitter` seconds so multiple instances do not update at once.
+    pub fn next_after_with_jitter(
+        &self,
+        time: DateTime<Utc>,
+        jitter: u32,
+    ) -> DateTime<Utc> {
+        let delay = rand::thread_rng()
+            .gen_range(0, u64::from(jitter) + 1);
+        self.next_after(time) + Duration::seconds(delay as i64)
+    }
+}
+
+/// State shared between the scheduler and the request handlers.
+#[derive(Debug, Clone, Default)]
+pub struct SchedulerState {
+    pub next_runs: NextRuns,
+    pub update_runs: UpdateRuns,
+    pub reports: ReportQueue,
+}
+
+/// The planned update of every provider, shared between the scheduler
+/// and the stats routes.
+#[derive(Debug, Clone, Default)]
+pub struct NextRuns(Arc<Mutex<HashMap<BackendType, NextUpdate>>>);
+
+impl NextRuns {
+    pub fn plan(
+        &self,
+        backend_type: BackendType,
+        name: &str,
+        next_run: DateTime<Utc>,
+    ) {
+        self.0.lock().expect("Poisoned lock").insert(
+            backend_type,
+            NextUpdate {
+                backend_type,
+                name: name.to_string(),
+                next_run: next_run.naive_utc(),
+            },
+        );
+    }
+
+    /// Whether the provider is due. Providers without a planned run are
+    /// never due.
+    pub fn is_due(
+        &self,
+        backend_type: BackendType,
+        now: DateTime<Utc>,
+    ) -> bool {
+        self.0
+            .lock()
+            .expect("Poisoned lock")
+            .get(&backend_type)
+            .map(|next| next.next_run <= now.naive_utc())
+            .unwrap_or(false)
+    }
+
+    /// Every planned update, the earliest first.
+    pub fn list(&self) -> Vec<NextUpdate> {
+        let mut values: Vec<NextUpdate> = self
+            .0
+            .lock()
+            .expect("Poisoned lock")
+            .values()
+            .cloned()
+            .collect();
+        values.sort_by_key(|value| value.next_run);
+        values
+    }
+
+    /// Every provider which is due but not yet updated.
+    pub fn due(&self, now: DateTime<Utc>) -> Vec<NextUpdate> {
+        self.list()
+            .into_iter()
+            .filter(|value| value.next_run <= now.naive_utc())
+            .collect()
+    }
+
+    /// Plans an immediate run of the given providers, every provider
+    /// if none is given. Returns the unknown name if a provider is not
+    /// configured.
+    pub fn queue(
+        &self,
+        names: &[String],
+        now: DateTime<Utc>,
+    ) -> Result<Vec<NextUpdate>, String> {
+        let mut values = self.0.lock().expect("Poisoned lock");
+        if let Some(name) = names.iter().find(|name| {
+            !values.keys().any(|backend_type| {
+                backend_type.to_string() == **name
+            })
+        }) {
+            return Err(name.clone());
+        }
+        let mut queued: Vec<NextUpdate> = values
+            .values_mut()
+            .filter(|value| {
+                names.is_empty()
+                    || names
+                        .contains(&value.backend_type.to_string())
+            })
+            .map(|value| {
+                value.next_run =
+                    value.next_run.min(now.naive_utc());
+                value.clone()
+            })
+            .collect();
+        queued.sort_by_key(|value| value.next_run);
+        Ok(queued)
+    }
+}
+
+/// The current and the last update run.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateRuns(
+    Arc<Mutex<(Option<UpdateRun>, Option<UpdateRun>)>>,
+);
+
+impl UpdateRuns {
+    pub fn start(&self, providers: Vec<BackendType>) {
+        self.0.lock().expect("Poisoned lock").0 =
+            Some(UpdateRun {
+                providers,
+                started: Utc::now().naive_utc(),
+                finished: None,
+                inserted: 0,
+                refreshed: 0,
+                deleted: 0,
+                errors: 0,
+            });
+    }
+
+    /// Marks the current run as finished and keeps it as the last one.
+    pub fn finish(&self, summary: UpdateSummary) {
+        let mut runs = self.0.lock().expect("Poisoned lock");
+        if let Some(mut run) = runs.0.take() {
+            run.finished = Some(Utc::now().naive_utc());
+            run.inserted = summary.inserted;
+            run.refreshed = summary.refreshed;
+            run.deleted = summary.deleted;
+            run.errors = summary.errors;
+            runs.1 = Some(run);
+        }
+    }
+
+    pub fn status(
+        &self,
+        queued: Vec<NextUpdate>,
+    ) -> UpdateStatus {
+        let runs = self.0.lock().expect("Poisoned lock");
+        UpdateStatus {
+            current: runs.0.clone(),
+            last: runs.1.clone(),
+            queued,
+        }
+    }
+}
+
+/// Reports waiting to be forwarded to AbuseIPDB. Reports are only accepted if AbuseIPDB is
+/// configured. The queue is only kept in memory and lost on restart.
+#[derive(Debug, Clone, Default)]
+pub struct ReportQueue {
+    enabled: bool,
+    queue: Arc<Mutex<QueuedReports>>,
+}
+
+#[derive(Debug, Default)]
+struct QueuedReports {
+    pending: VecDeque<Report>,
+    /// The time every ip was last taken to be forwarded.
+    reported: HashMap<IpAddr, NaiveDateTime>,
+}
+
+impl ReportQueue {
+    pub fn new(enabled: bool) -> Self {
+        ReportQueue {
+            enabled,
+            queue: Arc::default(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Queues the report unless the ip is already queued or was
+    /// forwarded within the report window. Returns whether it was
+    /// queued.
+    pub fn push(&self, report: Report) -> bool {
+        let mut queue = self.queue.lock().expect("Poisoned lock");
+        let window_start = report.created
+            - Duration::minutes(REPORT_WINDOW_MINUTES);
+        queue
+            .reported
+            .retain(|_, reported| *reported > window_start);
+        if queue.reported.contains_key(&report.ip)
+            || queue
+                .pending
+                .iter()
+                .any(|queued| queued.ip == report.ip)
+        {
+            return false;
+        }
+        queue.pending.push_back(report);
+        true
+    }
+
+    /// Marks the ip as forwarded, further reports within the report
+    /// window are dropped.
+    pub fn mark_reported(&self, ip: IpAddr, time: NaiveDateTime) {
+        self.queue
+            .lock()
+            .expect("Poisoned lock")
+            .reported
+            .insert(ip, time);
+    }
+
+    /// Takes every queued report. The ips count as reported from now
+    /// on, so reports arriving while they are forwarded are dropped.
+    pub fn drain(&self) -> Vec<Report> {
+        let now = Utc::now().naive_utc();
+        let mut queue = self.queue.lock().expect("Poisoned lock");
+        let reports: Vec<Report> =
+            queue.pending.drain(..).collect();
+        for report in &reports {
+            queue.reported.insert(report.ip, now);
+        }
+        reports
+    }
+
+    /// Accepts new reports of an ip whose report was rejected.
+    pub fn release(&self, ip: IpAddr) {
+        self.queue
+            .lock()
+            .expect("Poisoned lock")
+            .reported
+            .remove(&ip);
+    }
+
+    /// Queues reports again which could not be forwarded, ahead of
+    /// newer reports.
+    pub fn requeue(&self, reports: Vec<Report>) {
+        let mut queue = self.queue.lock().expect("Poisoned lock");
+        for report in reports.into_iter().rev() {
+            queue.reported.remove(&report.ip);
+            queue.pending.push_front(report);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone};
+
+    fn time(hour: u32, minute: u32) -> DateTime<Utc> {
+        // 2026-10-17 is a Saturday
+        Utc.from_utc_datetime(
+            &NaiveDate::from_ymd_opt(2026, 10, 17)
+                .and_then(|date| {
+                    date.and_hms_opt(hour, minute, 0)
+                })
+                .expect("Invalid date"),
+        )
+    }
+
+    fn report(ip: &str, created: NaiveDateTime) -> Report {
+        Report {
+            ip: ip.parse().expect("Invalid ip"),
+            categories: vec![18],
+            comment: None,
+            created,
+        }
+    }
+
+    #[test]
+    fn parse_adds_seconds_to_five_fields() {
+        let schedule = Schedule::parse("30 4 * * *")
+            .expect("Invalid schedule");
+        assert_eq!(schedule.next_after(time(3, 0)), time(4, 30));
+        assert_eq!(
+            schedule.next_after(time(4, 30)),
+            time(4, 30) + Duration::days(1)
+        );
+    }
+
+    #[test]
+    fn parse_keeps_six_fields() {
+        let schedule = Schedule::parse("15 30 4 * * *")
+            .expect("Invalid schedule");
+        assert_eq!(
+            schedule.next_after(time(3, 0)),
+            time(4, 30) + Duration::seconds(15)
+        );
+    }
+
+    #[test]
+    fn parse_rejects_invalid_cron() {
+        assert!(Schedule::parse("61 * * * *").is_err());
+        assert!(Schedule::parse("* * *").is_err());
+        assert!(Schedule::parse("").is_err());
+    }
+
+    #[test]
+    fn weekdays_start_at_sunday() {
+        // 1 is Sunday for the cron crate, not Monday
+        let schedule = Schedule::parse("0 4 * * 1")
+            .expect("Invalid schedule");
+        assert_eq!(
+            schedule.next_after(time(12, 0)),
+            time(4, 0) + Duration::days(1)
+        );
+        let schedule = Schedule::parse("0 4 * * Mon-Fri")
+            .expect("Invalid schedule");
+        assert_eq!(
+            schedule.next_after(time(12, 0)),
+            time(4, 0) + Duration::days(2)
+        );
+    }
+
+    #[test]
+    fn parse_interval() {
+        let schedule = Schedule::parse("every 30m")
+            .expect("Invalid schedule");
+        assert_eq!(schedule.next_after(time(3, 0)), time(3, 30));
+        let schedule = Schedule::parse(" every 2 h ")
+            .expect("Invalid schedule");
+        assert_eq!(schedule.next_after(time(3, 0)), time(5, 0));
+        let schedule = Schedule::parse("every 1d")
+            .expect("Invalid schedule");
+        assert_eq!(
+            schedule.next_after(time(3, 0)),
+            time(3, 0) + Duration::days(1)
+        );
+        let schedule = Schedule::parse("every 90s")
+            .expect("Invalid schedule");
+        assert_eq!(
+            schedule.next_after(time(3, 0)),
+            time(3, 1) + Duration::seconds(30)
+        );
+    }
+
+    #[test]
+    fn parse_rejects_invalid_interval() {
+        assert!(Schedule::parse("every ").is_err());
+        assert!(Schedule::parse("every m").is_err());
+        assert!(Schedule::parse("every 0m").is_err());
+        assert!(Schedule::parse("every -5m").is_err());
+        assert!(Schedule::parse("every 30").is_err());
+        assert!(Schedule::parse("every 30w").is_err());
+        assert!(Schedule::parse("every 30µ").is_err());
+    }
+
+    #[test]
+    fn jitter_only_delays() {
+        let schedule = Schedule::parse("every 30m")
+            .expect("Invalid schedule");
+        let next =
+            schedule.next_after_with_jitter(time(3, 0), 60);
+        assert!(next >= time(3, 30) && next <= time(3, 31));
+        assert_eq!(
+            schedule.next_after_with_jitter(time(3, 0), 0),
+            time(3, 30)
+        );
+    }
+
+    #[test]
+    fn push_drops_queued_ip() {
+        let queue = ReportQueue::new(true);
+        let now = Utc::now().naive_utc();
+        assert!(queue.push(report("192.0.2.1", now)));
+        assert!(!queue.push(report("192.0.2.1", now)));
+        assert!(queue.push(report("192.0.2.2", now)));
+        assert_eq!(queue.drain().len(), 2);
+    }
+
+    #[test]
+    fn push_drops_forwarded_ip_within_window() {
+        let queue = ReportQueue::new(true);
+        let now = Utc::now().naive_utc();
+        assert!(queue.push(report("192.0.2.1", now)));
+        let drained = queue.drain();
+        assert_eq!(drained.len(), 1);
+        // Still being forwarded
+        assert!(!queue.push(report("192.0.2.1", now)));
+        queue.mark_reported(drained[0].ip, now);
+        assert!(!queue.push(report(
+            "192.0.2.1",
+            now + Duration::minutes(REPORT_WINDOW_MINUTES - 1)
+        )));
+        assert!(queue.push(report(
+            "192.0.2.1",
+            now + Duration::minutes(REPORT_WINDOW_MINUTES + 1)
+        )));
+    }
+
+    #[test]
+    fn release_accepts_rejected_ip() {
+        let queue = ReportQueue::new(true);
+        let now = Utc::now().naive_utc();
+        assert!(queue.push(report("192.0.2.1", now)));
+        let drained = queue.drain();
+        queue.release(drained[0].ip);
+        assert!(queue.push(report("192.0.2.1", now)));
+    }
+
+    #[test]
+    fn requeue_keeps_order() {
+        let queue = ReportQueue::new(true);
+        let now = Utc::now().naive_utc();
+        assert!(queue.push(report("192.0.2.1", now)));
+        assert!(queue.push(report("192.0.2.2", now)));
+        let drained = queue.drain();
+        assert!(queue.push(report("192.0.2.3", now)));
+        queue.requeue(drained);
+        // Requeued ips are still deduplicated by the queue
+        assert!(!queue.push(report("192.0.2.1", now)));
+        let ips: Vec<String> = queue
+            .drain()
+            .into_iter()
+            .map(|report| report.ip.to_string())
+            .collect();
+        assert_eq!(
+            ips,
+            vec!["192.0.2.1", "192.0.2.2", "192.0.2.3"]
+        );
+    }
+}
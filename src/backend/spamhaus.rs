@@ -0,0 +1,255 @@
+use crate::{
+    backend::provider::{fetch_text, Provider},
+    helper::parse_network,
+    types::{backend_type::BackendType, blacklist::Blacklist},
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use log::{info, warn};
+use reqwest::blocking::Client;
+use std::sync::{Arc, Mutex};
+
+const DROP_URL: &str = "https://www.spamhaus.org/drop/drop.txt";
+const EDROP_URL: &str = "https://www.spamhaus.org/drop/edrop.txt";
+
+/// Spamhaus DROP and EDROP lists.
+///
+/// Both lists contain one network per line followed by the SBL
+/// reference, e.g. `1.10.16.0/20 ; SBL256894`. The reference is kept
+/// as reason of the entry. The `Expires` header comment of the list is
+/// used to skip updates until a new version is available.
+#[derive(Debug, Clone)]
+pub struct Spamhaus {
+    name: &'static str,
+    url: String,
+    backend_type: BackendType,
+    expires: Arc<Mutex<Option<NaiveDateTime>>>,
+}
+
+impl Spamhaus {
+    /// Parses `drop` or `edrop`, optionally followed by `=<url>` to
+    /// fetch the list from a different location.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut parts = spec.splitn(2, '=');
+        let list = parts.next().unwrap_or("").trim();
+        let url = parts.next().map(str::trim);
+        Self::new(list, url)
+    }
+
+    pub fn new(
+        list: &str,
+        url: Option<&str>,
+    ) -> Result<Self, String> {
+        let (name, default_url, backend_type) = match list {
+            "drop" => (
+                "spamhaus drop",
+                DROP_URL,
+                BackendType::SpamhausDrop,
+            ),
+            "edrop" => (
+                "spamhaus edrop",
+                EDROP_URL,
+                BackendType::SpamhausEdrop,
+            ),
+            _ => return Err(format!("Unknown list: {}", list)),
+        };
+        Ok(Spamhaus {
+            name,
+            url: url.unwrap_or(default_url).into(),
+            backend_type,
+            expires: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    fn parse_line(&self, line: &str) -> Option<Blacklist> {
+        let mut parts = line.splitn(2, ';');
+        let network = parts.next().unwrap_or("").trim();
+        let reason = parts
+            .next()
+            .map(str::trim)
+            .filter(|reason| !reason.is_empty())
+            .map(String::from);
+        if network.is_empty() {
+            if let Some(expires) =
+                reason.as_ref().and_then(|r| parse_expires(r))
+            {
+                *self.expires.lock().expect("Poisoned lock") =
+                    Some(expires);
+            }
+            return None;
+        }
+        let (addr, prefix) = match parse_network(network) {
+            Some(network) => network,
+            None => {
+                warn!(
+                    "Invalid network in {}: {}",
+                    self.name, line
+                );
+                return None;
+            }
+        };
+        Blacklist::new_network(
+            addr,
+            prefix,
+            self.backend_type,
+            reason,
+        )
+    }
+}
+
+fn parse_expires(comment: &str) -> Option<NaiveDateTime> {
+    let mut parts = comment.splitn(2, ':');
+    if parts.next()?.trim() != "Expires" {
+        return None;
+    }
+    DateTime::parse_from_rfc2822(parts.next()?.trim())
+        .ok()
+        .map(|date| date.with_timezone(&Utc).naive_utc())
+}
+
+impl Provider for Spamhaus {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn backend_type(&self) -> BackendType {
+        self.backend_type
+    }
+
+    fn fetch(&self, client: &Client) -> Option<Vec<Blacklist>> {
+        let expires =
+            *self.expires.lock().expect("Poisoned lock");
+        if let Some(expires) = expires {
+            if Utc::now().naive_utc() < expires {
+                info!(
+                    "Skipping {}, current list expires at {}",
+                    self.name, expires
+                );
+                return Some(Vec::new());
+            }
+        }
+        let response = fetch_text(client, self.name, &self.url)?;
+        Some(
+            response
+                .lines()
+                .filter_map(|line| self.parse_line(line))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::{env, fs, process};
+
+    const LIST: &str = "; Spamhaus DROP List 2026/10/17 - (c) 2026 The Spamhaus Project
+; Last-Modified: Sat, 17 Oct 2026 00:00:00 GMT
+; Expires: Sat, 17 Oct 2026 12:00:00 GMT
+1.10.16.0/20 ; SBL256894
+2.56.192.0/22 ; SBL459831
+";
+
+    fn expires() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 17)
+            .and_then(|date| date.and_hms_opt(12, 0, 0))
+            .expect("Invalid date")
+    }
+
+    #[test]
+    fn parse_line_keeps_reference() {
+        let list =
+            Spamhaus::new("drop", None).expect("Invalid list");
+        let entry = list
+            .parse_line("1.10.16.0/20 ; SBL256894")
+            .expect("Entry expected");
+        assert_eq!(
+            entry.to_plain().as_deref(),
+            Some("1.10.16.0/20")
+        );
+        assert_eq!(entry.backend_type, BackendType::SpamhausDrop);
+        assert_eq!(entry.reason.as_deref(), Some("SBL256894"));
+    }
+
+    #[test]
+    fn parse_line_skips_invalid_networks() {
+        let list =
+            Spamhaus::new("edrop", None).expect("Invalid list");
+        assert!(list.parse_line("1.10.16.0/33 ; SBL1").is_none());
+        assert!(list.parse_line("not a network").is_none());
+        assert!(list.parse_line("").is_none());
+    }
+
+    #[test]
+    fn parse_line_reads_expires_comment() {
+        let list =
+            Spamhaus::new("drop", None).expect("Invalid list");
+        assert!(list
+            .parse_line(
+                "; Expires: Sat, 17 Oct 2026 12:00:00 GMT"
+            )
+            .is_none());
+        assert_eq!(
+            *list.expires.lock().expect("Poisoned lock"),
+            Some(expires())
+        );
+    }
+
+    #[test]
+    fn parse_expires_ignores_other_comments() {
+        assert_eq!(
+            parse_expires(
+                "Expires: Sat, 17 Oct 2026 12:00:00 GMT"
+            ),
+            Some(expires())
+        );
+        assert_eq!(
+            parse_expires(
+                "Last-Modified: Sat, 17 Oct 2026 00:00:00 GMT"
+            ),
+            None
+        );
+        assert_eq!(parse_expires("Expires: tomorrow"), None);
+    }
+
+    #[test]
+    fn fetch_reads_file_url() {
+        let path = env::temp_dir().join(format!(
+            "blacklistd-drop-{}.txt",
+            process::id()
+        ));
+        fs::write(&path, LIST).expect("Unable to write list");
+        let url = format!("file://{}", path.display());
+        let list = Spamhaus::new("drop", Some(&url))
+            .expect("Invalid list");
+        let entries = list.fetch(&Client::new());
+        fs::remove_file(&path).expect("Unable to remove list");
+        let networks: Vec<String> = entries
+            .expect("List expected")
+            .iter()
+            .filter_map(Blacklist::to_plain)
+            .collect();
+        assert_eq!(
+            networks,
+            vec!["1.10.16.0/20", "2.56.192.0/22"]
+        );
+        assert_eq!(
+            *list.expires.lock().expect("Poisoned lock"),
+            Some(expires())
+        );
+    }
+
+    #[test]
+    fn fetch_skips_list_until_expired() {
+        let list = Spamhaus::new(
+            "drop",
+            Some("file:///nonexistent/blacklistd-drop.txt"),
+        )
+        .expect("Invalid list");
+        *list.expires.lock().expect("Poisoned lock") = Some(
+            Utc::now().naive_utc() + chrono::Duration::hours(1),
+        );
+        let entries = list.fetch(&Client::new());
+        assert_eq!(entries.map(|entries| entries.len()), Some(0));
+    }
+}
@@ -0,0 +1,1202 @@
+use crate::{
+    args::CliArguments,
+    backend::{
+        provider::{Provider, Recheck},
+        reserved::ReservedNetworks,
+    },
+    db_op,
+    helper::{get_elapsed_time, networks_overlap},
+    middleware::diesel::{DBType, DieselPooledConnection},
+    schema::{
+        allowlist, blacklist::dsl::*, blacklist_change,
+        blacklist_history, blacklist_source, rate_limit,
+    },
+    types::{
+        allowlist::{is_allowlisted, Allowlist},
+        backend_type::BackendType,
+        blacklist::Blacklist,
+        change::{ChangeType, NewChange},
+        history::{HistoryEvent, NewHistory},
+        ip_type::IpType,
+        rate_limit::{RateLimit, RATE_LIMIT_RESERVE},
+        source::Source,
+    },
+};
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{
+    sql_query, BoolExpressionMethods, Connection,
+    ExpressionMethods, QueryDsl, RunQueryDsl,
+};
+use log::{debug, error, info, warn};
+use reqwest::blocking::Client;
+use std::{any::Any, collections::HashSet, fmt, ops::AddAssign};
+use time::OffsetDateTime;
+
+/// Maximum number of events per history insert, keeping the bind
+/// parameters of a single statement below the database limits.
+const HISTORY_PER_INSERT: usize = 1000;
+
+/// Number of entries touched by an update.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UpdateSummary {
+    pub inserted: usize,
+    pub refreshed: usize,
+    pub deleted: usize,
+    pub errors: usize,
+}
+
+impl AddAssign for UpdateSummary {
+    fn add_assign(&mut self, other: Self) {
+        self.inserted += other.inserted;
+        self.refreshed += other.refreshed;
+        self.deleted += other.deleted;
+        self.errors += other.errors;
+    }
+}
+
+impl fmt::Display for UpdateSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} inserted, {} refreshed, {} deleted, {} errors",
+            self.inserted,
+            self.refreshed,
+            self.deleted,
+            self.errors
+        )
+    }
+}
+
+/// Fetches the list of the given provider. Its old entries are
+/// rechecked at once if `recheck` is set, otherwise they are left to
+/// `recheck_provider`. A list which could not be fetched counts as an
+/// error.
+pub fn update_provider(
+    args: &CliArguments,
+    provider: &dyn Provider,
+    db_conn: &dyn Any,
+    db_type: DBType,
+    client: &Client,
+    recheck: bool,
+) -> UpdateSummary {
+    info!("Fetching {}", provider.name());
+    let time = OffsetDateTime::now();
+    let mut summary = UpdateSummary::default();
+    provider.restore_rate_limits(load_rate_limits(
+        db_conn,
+        db_type,
+        provider.backend_type(),
+    ));
+    match provider.fetch(client) {
+        Some(entries) => {
+            debug!("Storing {}", provider.name());
+            summary += store_entries(
+                args,
+                db_conn,
+                &entries,
+                provider.backend_type(),
+                db_type,
+            );
+        }
+        None => summary.errors += 1,
+    }
+    if recheck && provider.supports_check() {
+        debug!("Updating old {} ips", provider.name());
+        summary += update_old_ips(
+            args, provider, db_conn, db_type, client, None,
+        );
+    }
+    store_rate_limits(db_conn, db_type, &provider.rate_limits());
+    info!(
+        "{} completed after {} s",
+        provider.name(),
+        get_elapsed_time(time)
+    );
+    summary
+}
+
+/// Rechecks the next share of the old entries of the given provider.
+/// Called every `interval`, the remaining check budget is spread
+/// evenly until it resets.
+pub fn recheck_provider(
+    args: &CliArguments,
+    provider: &dyn Provider,
+    db_conn: &dyn Any,
+    db_type: DBType,
+    client: &Client,
+    interval: Duration,
+) -> UpdateSummary {
+    provider.restore_rate_limits(load_rate_limits(
+        db_conn,
+        db_type,
+        provider.backend_type(),
+    ));
+    let batch = recheck_batch(
+        args.recheck_budget,
+        provider.check_budget(),
+        Utc::now().naive_utc(),
+        interval,
+    );
+    if batch == 0 {
+        return UpdateSummary::default();
+    }
+    debug!("Rechecking up to {} {} ips", batch, provider.name());
+    let summary = update_old_ips(
+        args,
+        provider,
+        db_conn,
+        db_type,
+        client,
+        Some(batch),
+    );
+    store_rate_limits(db_conn, db_type, &provider.rate_limits());
+    // Entries without any remaining source are removed right away, so
+    // incremental clients do not wait for the daily cleanup.
+    if summary.deleted > 0 {
+        let deleted = delete_unsourced(db_conn, db_type);
+        debug!("Removed {} unsourced ips", deleted);
+    }
+    summary
+}
+
+/// Number of checks for the next interval. Uses the budget reported by
+/// the provider if it is known, the configured daily budget otherwise.
+fn recheck_batch(
+    daily_budget: u32,
+    budget: Option<RateLimit>,
+    now: NaiveDateTime,
+    interval: Duration,
+) -> i64 {
+    let interval = interval.num_seconds().max(1);
+    match budget {
+        Some(RateLimit {
+            remaining: Some(remaining),
+            reset: Some(reset),
+            ..
+        }) if reset > now => {
+            let slots = ((reset - now).num_seconds() + interval
+                - 1)
+                / interval;
+            let remaining = i64::from(
+                (remaining - RATE_LIMIT_RESERVE).max(0),
+            );
+            (remaining + slots - 1) / slots.max(1)
+        }
+        _ => {
+            let slots =
+                Duration::days(1).num_seconds() / interval;
+            (i64::from(daily_budget) + slots - 1) / slots.max(1)
+        }
+    }
+}
+
+fn store_entries(
+    args: &CliArguments,
+    db_conn: &dyn Any,
+    entries: &[Blacklist],
+    provider_type: BackendType,
+    db_type: DBType,
+) -> UpdateSummary {
+    let mut summary = UpdateSummary::default();
+    let mut operations = 0;
+    let mut allowlisted = 0;
+    let mut reserved = 0;
+    let mut errors = 0;
+
+    let reserved_networks = ReservedNetworks::new();
+
+    let allowed = db_op!(db_conn, db_type, conn {
+        allowlist::table.load::<Allowlist>(conn)
+    });
+    let allowed = match allowed {
+        Ok(allowed) => allowed,
+        Err(err) => {
+            error!("Unable to load allowlist with: {:#?}", err);
+            summary.errors += 1;
+            return summary;
+        }
+    };
+    let existing = db_op!(db_conn, db_type, conn {
+        blacklist
+            .select((ip, ip_type, prefix))
+            .load::<(Vec<u8>, IpType, i16)>(conn)
+    });
+    let mut existing: HashSet<(Vec<u8>, IpType, i16)> =
+        match existing {
+            Ok(existing) => existing.into_iter().collect(),
+            Err(err) => {
+                error!("Unable to load ips with: {:#?}", err);
+                summary.errors += 1;
+                return summary;
+            }
+        };
+    let sourced = db_op!(db_conn, db_type, conn {
+        blacklist_source::table
+            .select((
+                blacklist_source::ip,
+                blacklist_source::ip_type,
+                blacklist_source::prefix,
+            ))
+            .filter(blacklist_source::backend_type.eq(provider_type))
+            .load::<(Vec<u8>, IpType, i16)>(conn)
+    });
+    let sourced: HashSet<(Vec<u8>, IpType, i16)> = match sourced {
+        Ok(sourced) => sourced.into_iter().collect(),
+        Err(err) => {
+            error!("Unable to load sources with: {:#?}", err);
+            summary.errors += 1;
+            return summary;
+        }
+    };
+
+    let mut events = Vec::new();
+    for entry in entries {
+        if args.reserved_filter
+            && reserved_networks.contains(entry)
+        {
+            reserved += 1;
+            continue;
+        }
+        if is_allowlisted(&allowed, entry) {
+            allowlisted += 1;
+            continue;
+        }
+        operations += 1;
+        let previous_errors = errors;
+        let source = Source::from(entry);
+        match db_type {
+            DBType::POSTGRES => {
+                let conn = db_conn
+                    .downcast_ref::<DieselPooledConnection<
+                        diesel::pg::PgConnection,
+                    >>()
+                    .expect("Downcast failed");
+
+                let _ = diesel::insert_into(blacklist)
+                    .values(entry)
+                    .on_conflict((ip, ip_type, prefix))
+                    .do_update()
+                    .set((
+                        last_update.eq(entry.last_update),
+                        expires.eq(entry.expires),
+                    ))
+                    .execute(conn)
+                    .and_then(|_| {
+                        diesel::insert_into(
+                            blacklist_source::table,
+                        )
+                        .values(&source)
+                        .on_conflict((
+                            blacklist_source::ip,
+                            blacklist_source::ip_type,
+                            blacklist_source::prefix,
+                            blacklist_source::backend_type,
+                        ))
+                        .do_update()
+                        .set(&source)
+                        .execute(conn)
+                    })
+                    .map_err(|err| {
+                        errors += 1;
+                        error!(
+                            "Unable to update ip: {} with: {:#?}",
+                            entry
+                                .to_plain()
+                                .unwrap_or_else(String::new),
+                            err
+                        )
+                    });
+            }
+            DBType::MYSQL => {
+                let conn = db_conn
+                    .downcast_ref::<DieselPooledConnection<
+                        diesel::mysql::MysqlConnection,
+                    >>()
+                    .expect("Downcast failed");
+
+                let _ = diesel::insert_or_ignore_into(blacklist)
+                    .values(entry)
+                    .execute(conn)
+                    .and_then(|_| {
+                        diesel::update(entry)
+                            .set((
+                                last_update.eq(entry.last_update),
+                                expires.eq(entry.expires),
+                            ))
+                            .execute(conn)
+                    })
+                    .and_then(|_| {
+                        diesel::insert_or_ignore_into(
+                            blacklist_source::table,
+                        )
+                        .values(&source)
+                        .execute(conn)
+                    })
+                    .and_then(|_| {
+                        diesel::update(&source)
+                            .set(&source)
+                            .execute(conn)
+                    })
+                    .map_err(|err| {
+                        errors += 1;
+                        error!(
+                            "Unable to update ip: {} with: {:#?}",
+                            entry
+                                .to_plain()
+                                .unwrap_or_else(String::new),
+                            err
+                        )
+                    });
+            }
+            DBType::SQLITE => {
+                let conn = db_conn
+                    .downcast_ref::<DieselPooledConnection<
+                        diesel::sqlite::SqliteConnection,
+                    >>()
+                    .expect("Downcast failed");
+
+                let _ = diesel::insert_or_ignore_into(blacklist)
+                    .values(entry)
+                    .execute(conn)
+                    .and_then(|_| {
+                        diesel::update(entry)
+                            .set((
+                                last_update.eq(entry.last_update),
+                                expires.eq(entry.expires),
+                            ))
+                            .execute(conn)
+                    })
+                    .and_then(|_| {
+                        diesel::insert_or_ignore_into(
+                            blacklist_source::table,
+                        )
+                        .values(&source)
+                        .execute(conn)
+                    })
+                    .and_then(|_| {
+                        diesel::update(&source)
+                            .set(&source)
+                            .execute(conn)
+                    })
+                    .map_err(|err| {
+                        errors += 1;
+                        error!(
+                            "Unable to update ip: {} with: {:#?}",
+                            entry
+                                .to_plain()
+                                .unwrap_or_else(String::new),
+                            err
+                        )
+                    });
+            }
+        }
+        if errors != previous_errors {
+            continue;
+        }
+        let key = (entry.ip.clone(), entry.ip_type, entry.prefix);
+        let event = if sourced.contains(&key) {
+            summary.refreshed += 1;
+            HistoryEvent::Refreshed
+        } else {
+            summary.inserted += 1;
+            HistoryEvent::Added
+        };
+        events.push(NewHistory::new(&source, event, entry.score));
+        if existing.insert(key) {
+            let change = NewChange::new(
+                &entry.ip,
+                entry.ip_type,
+                entry.prefix,
+                ChangeType::Added,
+            );
+            let _ = db_op!(db_conn, db_type, conn {
+                diesel::insert_into(blacklist_change::table)
+                    .values(&change)
+                    .execute(conn)
+            })
+            .map_err(|err| {
+                error!(
+                    "Unable to record change of ip: {} with: {:#?}",
+                    entry.to_plain().unwrap_or_else(String::new),
+                    err
+                )
+            });
+        }
+    }
+    record_history(db_conn, db_type, &events);
+    debug!(
+        "Store Completed. {} Inserts or Updates, {} Reserved, {} Allowlisted, {} Errors",
+        operations - errors,
+        reserved,
+        allowlisted,
+        errors
+    );
+    summary.errors += errors;
+    summary
+}
+
+/// Rechecks the sources of the given provider which were not updated
+/// for the expiration duration, at most `limit`. The oldest sources,
+/// which are the closest to the stale removal, go first. Sources no
+/// longer listed are removed.
+fn update_old_ips(
+    args: &CliArguments,
+    provider: &dyn Provider,
+    db_conn: &dyn Any,
+    db_type: DBType,
+    client: &Client,
+    limit: Option<i64>,
+) -> UpdateSummary {
+    let mut updated = 0;
+    let mut deleted = 0;
+    let mut update_error = 0;
+    let mut delete_error = 0;
+
+    let update_threshold = chrono::Utc::now()
+        .checked_sub_signed(chrono::Duration::days(i64::from(
+            args.expiration_days,
+        )))
+        .expect("Unable to substract days")
+        .naive_utc();
+    let filter = blacklist_source::table
+        .filter(
+            blacklist_source::last_update
+                .lt(update_threshold)
+                .and(
+                    blacklist_source::backend_type
+                        .eq(provider.backend_type()),
+                ),
+        )
+        .order_by(blacklist_source::last_update.asc())
+        .limit(limit.unwrap_or(i64::MAX));
+
+    db_op!(db_conn, db_type, conn {
+        update_old_ip(
+            provider,
+            || filter.load::<Source>(conn),
+            |source, recheck| {
+                updated += 1;
+                conn.transaction(|| {
+                    let updated = diesel::update(blacklist_source::table)
+                        .set(source)
+                        .filter(blacklist_source::ip.eq(&source.ip)
+                                .and(blacklist_source::ip_type.eq(source.ip_type))
+                                .and(blacklist_source::prefix.eq(source.prefix))
+                                .and(blacklist_source::backend_type.eq(source.backend_type)))
+                        .execute(conn)?;
+                    if recheck.reputation.score.is_some() {
+                        diesel::update(source)
+                            .set(&recheck.reputation)
+                            .execute(conn)?;
+                    }
+                    diesel::update(blacklist)
+                        .set(last_update.eq(source.last_update))
+                        .filter(ip.eq(&source.ip)
+                                .and(ip_type.eq(source.ip_type))
+                                .and(prefix.eq(source.prefix))
+                                .and(last_update.lt(source.last_update)))
+                        .execute(conn)?;
+                    insert_history(
+                        db_conn,
+                        db_type,
+                        &[NewHistory::new(
+                            source,
+                            HistoryEvent::RecheckPassed,
+                            recheck.reputation.score,
+                        )],
+                    )?;
+                    Ok(updated)
+                })
+            },
+            |source, recheck| {
+                deleted += 1;
+                conn.transaction(|| {
+                    let deleted = diesel::delete(
+                        blacklist_source::table.filter(
+                            blacklist_source::ip.eq(&source.ip)
+                                .and(blacklist_source::ip_type.eq(source.ip_type))
+                                .and(blacklist_source::prefix.eq(source.prefix))
+                                .and(blacklist_source::backend_type.eq(source.backend_type)),
+                        ),
+                    )
+                    .execute(conn)?;
+                    insert_history(
+                        db_conn,
+                        db_type,
+                        &[NewHistory::new(
+                            source,
+                            HistoryEvent::RecheckFailed,
+                            recheck.reputation.score,
+                        )],
+                    )?;
+                    Ok(deleted)
+                })
+            },
+            || update_error += 1,
+            || delete_error += 1,
+            client,
+        );
+    });
+    debug!(
+        "Update Completed. {} Updates, {} Deleted, {} Errors",
+        updated - update_error,
+        deleted - delete_error,
+        update_error + delete_error,
+    );
+    UpdateSummary {
+        inserted: 0,
+        refreshed: updated - update_error,
+        deleted: deleted - delete_error,
+        errors: update_error + delete_error,
+    }
+}
+
+fn update_old_ip<Q, E1, E2, U, D>(
+    provider: &dyn Provider,
+    query: Q,
+    mut update: U,
+    mut delete: D,
+    mut error_update: E1,
+    mut error_delete: E2,
+    client: &Client,
+) where
+    Q: FnOnce() -> Result<Vec<Source>, diesel::result::Error>,
+    U: FnMut(
+        &Source,
+        &Recheck,
+    ) -> Result<usize, diesel::result::Error>,
+    D: FnMut(
+        &Source,
+        &Recheck,
+    ) -> Result<usize, diesel::result::Error>,
+    E1: FnMut() -> (),
+    E2: FnMut() -> (),
+{
+    let values = query().unwrap_or_else(|err| {
+        error!("Unable to load ips with: {:#?}", err);
+        Vec::new()
+    });
+
+    for mut value in values {
+        match value.to_plain().and_then(|address| {
+            debug!("Checking ip: {}", address);
+            provider.check(client, &address)
+        }) {
+            Some(recheck) if recheck.listed => {
+                value.last_update =
+                    chrono::Utc::now().naive_utc();
+                let _ = update(&value, &recheck).map_err(|err| {
+                    error_update();
+                    error!(
+                        "Unable to update ip: {} with: {:#?}",
+                        value
+                            .to_plain()
+                            .unwrap_or_else(String::new),
+                        err
+                    )
+                });
+            }
+            Some(recheck) => {
+                let _ = delete(&value, &recheck).map_err(|err| {
+                    error_delete();
+                    error!(
+                        "Unable to delete ip: {} with: {:#?}",
+                        value
+                            .to_plain()
+                            .unwrap_or_else(String::new),
+                        err
+                    )
+                });
+            }
+            None => {
+                warn!(
+                    "Unable to fetch value for {}",
+                    value.to_plain().unwrap_or_else(String::new)
+                );
+                return;
+            }
+        }
+    }
+}
+
+/// Removes every entry which is no longer listed by any source. The
+/// removals are recorded as changes.
+pub fn delete_unsourced(
+    db_conn: &dyn Any,
+    db_type: DBType,
+) -> usize {
+    let record_query = sql_query(
+        "
+        INSERT INTO blacklist_change (ip, ip_type, prefix, change_type)
+        SELECT ip, ip_type, prefix, 1 FROM blacklist
+        WHERE NOT EXISTS (
+            SELECT 1 FROM blacklist_source
+            WHERE blacklist_source.ip = blacklist.ip
+                AND blacklist_source.ip_type = blacklist.ip_type
+                AND blacklist_source.prefix = blacklist.prefix
+        );
+    ",
+    );
+    let delete_query = sql_query(
+        "
+        DELETE FROM blacklist
+        WHERE NOT EXISTS (
+            SELECT 1 FROM blacklist_source
+            WHERE blacklist_source.ip = blacklist.ip
+                AND blacklist_source.ip_type = blacklist.ip_type
+                AND blacklist_source.prefix = blacklist.prefix
+        );
+    ",
+    );
+    db_op!(db_conn, db_type, conn {
+        conn.transaction(|| {
+            record_query.execute(conn)?;
+            delete_query.execute(conn)
+        })
+        .map_err(|err| {
+            error!("Unable to delete unsourced ips with: {:#?}", err)
+        })
+        .unwrap_or(0)
+    })
+}
+
+/// Loads the persisted request budgets of the given provider.
+pub fn load_rate_limits(
+    db_conn: &dyn Any,
+    db_type: DBType,
+    provider_type: BackendType,
+) -> Vec<RateLimit> {
+    db_op!(db_conn, db_type, conn {
+        rate_limit::table
+            .filter(rate_limit::backend_type.eq(provider_type))
+            .load::<RateLimit>(conn)
+    })
+    .unwrap_or_else(|err| {
+        error!("Unable to load rate limits with: {:#?}", err);
+        Vec::new()
+    })
+}
+
+/// Replaces the persisted request budgets. Failures are only logged.
+pub fn store_rate_limits(
+    db_conn: &dyn Any,
+    db_type: DBType,
+    limits: &[RateLimit],
+) {
+    for limit in limits {
+        let _ = db_op!(db_conn, db_type, conn {
+            conn.transaction::<_, diesel::result::Error, _>(|| {
+                diesel::delete(
+                    rate_limit::table.filter(
+                        rate_limit::backend_type
+                            .eq(limit.backend_type)
+                            .and(rate_limit::endpoint.eq(&limit.endpoint)),
+                    ),
+                )
+                .execute(conn)?;
+                diesel::insert_into(rate_limit::table)
+                    .values(limit)
+                    .execute(conn)
+            })
+        })
+        .map_err(|err| {
+            error!("Unable to store rate limit with: {:#?}", err)
+        });
+    }
+}
+
+/// Appends the given events to the history. Failures are only logged.
+pub fn record_history(
+    db_conn: &dyn Any,
+    db_type: DBType,
+    events: &[NewHistory],
+) {
+    if let Err(err) = insert_history(db_conn, db_type, events) {
+        error!("Unable to record history with: {:#?}", err);
+    }
+}
+
+/// Appends the given events to the history, batching up to
+/// `HISTORY_PER_INSERT` events per insert. SQLite does not support
+/// batch inserts, so they are inserted one by one there. Every insert
+/// runs in a transaction, which nests into the callers one.
+pub fn insert_history(
+    db_conn: &dyn Any,
+    db_type: DBType,
+    events: &[NewHistory],
+) -> Result<usize, diesel::result::Error> {
+    if events.is_empty() {
+        return Ok(0);
+    }
+    match db_type {
+        DBType::POSTGRES => {
+            let conn =
+                db_conn
+                    .downcast_ref::<DieselPooledConnection<
+                        diesel::pg::PgConnection,
+                    >>()
+                    .expect("Downcast failed");
+
+            conn.transaction(|| {
+                let mut inserted = 0;
+                for chunk in events.chunks(HISTORY_PER_INSERT) {
+                    inserted += diesel::insert_into(
+                        blacklist_history::table,
+                    )
+                    .values(chunk)
+                    .execute(conn)?;
+                }
+                Ok(inserted)
+            })
+        }
+        DBType::MYSQL => {
+            let conn = db_conn
+                .downcast_ref::<DieselPooledConnection<
+                    diesel::mysql::MysqlConnection,
+                >>()
+                .expect("Downcast failed");
+
+            conn.transaction(|| {
+                let mut inserted = 0;
+                for chunk in events.chunks(HISTORY_PER_INSERT) {
+                    inserted += diesel::insert_into(
+                        blacklist_history::table,
+                    )
+                    .values(chunk)
+                    .execute(conn)?;
+                }
+                Ok(inserted)
+            })
+        }
+        DBType::SQLITE => {
+            let conn = db_conn
+                .downcast_ref::<DieselPooledConnection<
+                    diesel::sqlite::SqliteConnection,
+                >>()
+                .expect("Downcast failed");
+
+            conn.transaction(|| {
+                for event in events {
+                    diesel::insert_into(blacklist_history::table)
+                        .values(event)
+                        .execute(conn)?;
+                }
+                Ok(events.len())
+            })
+        }
+    }
+}
+
+/// Adds the given allowlist entries, existing entries of the same
+/// network only get the new comment and creator. Entries hidden by a
+/// new allowlist entry are recorded as removed. Every statement runs in
+/// a single transaction.
+pub fn store_allowlist(
+    db_conn: &dyn Any,
+    db_type: DBType,
+    entries: &[Allowlist],
+) -> Result<(), diesel::result::Error> {
+    db_op!(db_conn, db_type, conn {
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let mut allowed =
+                allowlist::table.load::<Allowlist>(conn)?;
+            let values = blacklist.load::<Blacklist>(conn)?;
+            let mut changes = Vec::new();
+            for entry in entries {
+                let updated = diesel::update(
+                    allowlist::table.filter(
+                        allowlist::ip
+                            .eq(&entry.ip)
+                            .and(allowlist::ip_type.eq(entry.ip_type))
+                            .and(allowlist::prefix.eq(entry.prefix)),
+                    ),
+                )
+                .set((
+                    allowlist::comment.eq(&entry.comment),
+                    allowlist::created_by.eq(&entry.created_by),
+                ))
+                .execute(conn)?;
+                if updated > 0 {
+                    continue;
+                }
+                changes.extend(
+                    values
+                        .iter()
+                        .filter(|value| entry.overlaps(value))
+                        .filter(|value| !is_allowlisted(&allowed, value))
+                        .map(|value| {
+                            NewChange::new(
+                                &value.ip,
+                                value.ip_type,
+                                value.prefix,
+                                ChangeType::Removed,
+                            )
+                        }),
+                );
+                diesel::insert_into(allowlist::table)
+                    .values(entry)
+                    .execute(conn)?;
+                info!(
+                    "Added {} to the allowlist",
+                    entry.to_plain().unwrap_or_default()
+                );
+                allowed.push(entry.clone());
+            }
+            insert_changes(db_conn, db_type, &changes)?;
+            Ok(())
+        })
+    })
+}
+
+/// Removes the allowlist entry of the given network. Entries served
+/// again are recorded as added. Returns the number of removed entries,
+/// nothing is touched if the network is not allowlisted.
+pub fn remove_allowlist(
+    db_conn: &dyn Any,
+    db_type: DBType,
+    raw_ip: &[u8],
+    raw_type: IpType,
+    length: i16,
+) -> Result<usize, diesel::result::Error> {
+    db_op!(db_conn, db_type, conn {
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let deleted = diesel::delete(
+                allowlist::table.filter(
+                    allowlist::ip
+                        .eq(raw_ip)
+                        .and(allowlist::ip_type.eq(raw_type))
+                        .and(allowlist::prefix.eq(length)),
+                ),
+            )
+            .execute(conn)?;
+            if deleted == 0 {
+                return Ok(0);
+            }
+            let allowed = allowlist::table.load::<Allowlist>(conn)?;
+            let values = blacklist.load::<Blacklist>(conn)?;
+            let changes: Vec<NewChange> = values
+                .iter()
+                .filter(|value| {
+                    networks_overlap(
+                        (&value.ip, value.ip_type, value.prefix),
+                        (raw_ip, raw_type, length),
+                    )
+                })
+                .filter(|value| !is_allowlisted(&allowed, value))
+                .map(|value| {
+                    NewChange::new(
+                        &value.ip,
+                        value.ip_type,
+                        value.prefix,
+                        ChangeType::Added,
+                    )
+                })
+                .collect();
+            insert_changes(db_conn, db_type, &changes)?;
+            Ok(deleted)
+        })
+    })
+}
+
+/// Appends the given changes using a single insert. SQLite does not
+/// support batch inserts, so they are inserted one by one there.
+/// Callers run it inside their transaction.
+fn insert_changes(
+    db_conn: &dyn Any,
+    db_type: DBType,
+    changes: &[NewChange],
+) -> Result<usize, diesel::result::Error> {
+    if changes.is_empty() {
+        return Ok(0);
+    }
+    match db_type {
+        DBType::POSTGRES => {
+            let conn =
+                db_conn
+                    .downcast_ref::<DieselPooledConnection<
+                        diesel::pg::PgConnection,
+                    >>()
+                    .expect("Downcast failed");
+
+            diesel::insert_into(blacklist_change::table)
+                .values(changes)
+                .execute(conn)
+        }
+        DBType::MYSQL => {
+            let conn = db_conn
+                .downcast_ref::<DieselPooledConnection<
+                    diesel::mysql::MysqlConnection,
+                >>()
+                .expect("Downcast failed");
+
+            diesel::insert_into(blacklist_change::table)
+                .values(changes)
+                .execute(conn)
+        }
+        DBType::SQLITE => {
+            let conn = db_conn
+                .downcast_ref::<DieselPooledConnection<
+                    diesel::sqlite::SqliteConnection,
+                >>()
+                .expect("Downcast failed");
+
+            for change in changes {
+                diesel::insert_into(blacklist_change::table)
+                    .values(change)
+                    .execute(conn)?;
+            }
+            Ok(changes.len())
+        }
+    }
+}
+
+/// Stores a manual entry, replacing an existing entry of the same
+/// network. Every statement runs in a single transaction. A change is
+/// only recorded for new entries which are not allowlisted.
+pub fn store_manual(
+    db_conn: &dyn Any,
+    db_type: DBType,
+    entry: &Blacklist,
+) -> Result<(), diesel::result::Error> {
+    let source = Source::from(entry);
+    db_op!(db_conn, db_type, conn {
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let allowed = allowlist::table.load::<Allowlist>(conn)?;
+            let replaced = diesel::delete(
+                blacklist.filter(
+                    ip.eq(&entry.ip)
+                        .and(ip_type.eq(entry.ip_type))
+                        .and(prefix.eq(entry.prefix)),
+                ),
+            )
+            .execute(conn)?;
+            diesel::insert_into(blacklist).values(entry).execute(conn)?;
+            diesel::delete(
+                blacklist_source::table.filter(
+                    blacklist_source::ip
+                        .eq(&entry.ip)
+                        .and(blacklist_source::ip_type.eq(entry.ip_type))
+                        .and(blacklist_source::prefix.eq(entry.prefix))
+                        .and(
+                            blacklist_source::backend_type
+                                .eq(BackendType::Manual),
+                        ),
+                ),
+            )
+            .execute(conn)?;
+            diesel::insert_into(blacklist_source::table)
+                .values(&source)
+                .execute(conn)?;
+            // The entry only expires with the manual source if no
+            // provider lists it as well
+            if entry.expires.is_some() {
+                let listed = blacklist_source::table
+                    .filter(
+                        blacklist_source::ip
+                            .eq(&entry.ip)
+                            .and(blacklist_source::ip_type.eq(entry.ip_type))
+                            .and(blacklist_source::prefix.eq(entry.prefix))
+                            .and(
+                                blacklist_source::backend_type
+                                    .ne(BackendType::Manual),
+                            ),
+                    )
+                    .count()
+                    .get_result::<i64>(conn)?;
+                if listed > 0 {
+                    diesel::update(entry)
+                        .set(expires.eq(None::<NaiveDateTime>))
+                        .execute(conn)?;
+                }
+            }
+            diesel::insert_into(blacklist_history::table)
+                .values(&NewHistory::new(
+                    &source,
+                    HistoryEvent::Added,
+                    None,
+                ))
+                .execute(conn)?;
+            if replaced == 0 && !is_allowlisted(&allowed, entry) {
+                diesel::insert_into(blacklist_change::table)
+                    .values(&NewChange::new(
+                        &entry.ip,
+                        entry.ip_type,
+                        entry.prefix,
+                        ChangeType::Added,
+                    ))
+                    .execute(conn)?;
+            }
+            Ok(())
+        })
+    })
+}
+
+/// Removes every entry of the given network, regardless of the
+/// provider. Returns the number of removed entries, nothing is touched
+/// if the network is not listed.
+pub fn remove_network(
+    db_conn: &dyn Any,
+    db_type: DBType,
+    raw_ip: &[u8],
+    raw_type: IpType,
+    length: i16,
+) -> Result<usize, diesel::result::Error> {
+    let source_filter = blacklist_source::ip
+        .eq(raw_ip)
+        .and(blacklist_source::ip_type.eq(raw_type))
+        .and(blacklist_source::prefix.eq(length));
+    db_op!(db_conn, db_type, conn {
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let deleted = diesel::delete(
+                blacklist.filter(
+                    ip.eq(raw_ip)
+                        .and(ip_type.eq(raw_type))
+                        .and(prefix.eq(length)),
+                ),
+            )
+            .execute(conn)?;
+            if deleted == 0 {
+                return Ok(0);
+            }
+            let sources = blacklist_source::table
+                .filter(source_filter)
+                .load::<Source>(conn)?;
+            diesel::delete(blacklist_source::table.filter(source_filter))
+                .execute(conn)?;
+            for source in &sources {
+                diesel::insert_into(blacklist_history::table)
+                    .values(&NewHistory::new(
+                        source,
+                        HistoryEvent::Removed,
+                        None,
+                    ))
+                    .execute(conn)?;
+            }
+            diesel::insert_into(blacklist_change::table)
+                .values(&NewChange::new(
+                    raw_ip,
+                    raw_type,
+                    length,
+                    ChangeType::Removed,
+                ))
+                .execute(conn)?;
+            Ok(deleted)
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 17)
+            .and_then(|date| date.and_hms_opt(12, 0, 0))
+            .expect("Invalid date")
+    }
+
+    fn budget(
+        remaining: Option<i32>,
+        reset: Option<NaiveDateTime>,
+    ) -> Option<RateLimit> {
+        Some(RateLimit {
+            backend_type: BackendType::AbuseIpDb,
+            endpoint: String::from("check"),
+            request_limit: Some(1000),
+            remaining,
+            reset,
+            updated: now(),
+        })
+    }
+
+    #[test]
+    fn recheck_batch_spreads_daily_budget() {
+        // 288 slots of 5 minutes a day
+        let interval = Duration::minutes(5);
+        assert_eq!(recheck_batch(1000, None, now(), interval), 4);
+        assert_eq!(recheck_batch(288, None, now(), interval), 1);
+        assert_eq!(recheck_batch(0, None, now(), interval), 0);
+    }
+
+    #[test]
+    fn recheck_batch_spreads_remaining_budget() {
+        let interval = Duration::minutes(5);
+        let reset = Some(now() + Duration::hours(1));
+        // 100 checks left after the reserve over 12 slots
+        assert_eq!(
+            recheck_batch(
+                1000,
+                budget(Some(102), reset),
+                now(),
+                interval
+            ),
+            9
+        );
+        // The last slot before the reset gets everything
+        assert_eq!(
+            recheck_batch(
+                1000,
+                budget(
+                    Some(52),
+                    Some(now() + Duration::seconds(1))
+                ),
+                now(),
+                interval
+            ),
+            50
+        );
+    }
+
+    #[test]
+    fn recheck_batch_keeps_reserve() {
+        let interval = Duration::minutes(5);
+        let reset = Some(now() + Duration::hours(1));
+        for remaining in &[RATE_LIMIT_RESERVE, 1, 0, -5] {
+            assert_eq!(
+                recheck_batch(
+                    1000,
+                    budget(Some(*remaining), reset),
+                    now(),
+                    interval
+                ),
+                0
+            );
+        }
+    }
+
+    #[test]
+    fn recheck_batch_falls_back_without_reset() {
+        let interval = Duration::minutes(5);
+        for reset in
+            &[None, Some(now()), Some(now() - Duration::hours(1))]
+        {
+            assert_eq!(
+                recheck_batch(
+                    1000,
+                    budget(Some(0), *reset),
+                    now(),
+                    interval
+                ),
+                4
+            );
+        }
+        assert_eq!(
+            recheck_batch(
+                1000,
+                budget(None, Some(now() + Duration::hours(1))),
+                now(),
+                interval
+            ),
+            4
+        );
+    }
+
+    #[test]
+    fn recheck_batch_handles_empty_interval() {
+        assert_eq!(
+            recheck_batch(86400, None, now(), Duration::zero()),
+            1
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{
+    backend::provider::{fetch_text, Provider},
+    types::{
+        backend_type::{BackendType, FEED_OFFSET},
+        blacklist::Blacklist,
+    },
+};
+use reqwest::blocking::Client;
+
+/// A plain text feed containing one address per line.
+///
+/// Configured using a comma separated list of `key=value` pairs:
+///
+/// ```{none}
+/// id=<backend id>,url=<url>[,name=<name>][,comment=<prefix>][,column=<index>]
+/// ```
+///
+/// Everything after the comment prefix (default `#`) is ignored. If
+/// a column index is given, lines are split at whitespace and only the
+/// given column is used.
+#[derive(Debug, Clone)]
+pub struct UrlList {
+    name: String,
+    url: String,
+    comment: String,
+    column: Option<usize>,
+    backend_type: BackendType,
+}
+
+impl UrlList {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut id = None;
+        let mut url = None;
+        let mut name = None;
+        let mut comment = None;
+        let mut column = None;
+        for pair in spec.split(',') {
+            let mut kv = pair.splitn(2, '=');
+            let key = kv.next().unwrap_or("").trim();
+            let value = match kv.next() {
+                Some(value) => value.trim(),
+                None => {
+                    return Err(format!(
+                        "Missing value for {}",
+                        key
+                    ))
+                }
+            };
+            match key {
+                "id" => {
+                    id = Some(value.parse::<i16>().map_err(
+                        |_| format!("Invalid id: {}", value),
+                    )?)
+                }
+                "url" => url = Some(value.to_string()),
+                "name" => name = Some(value.to_string()),
+                "comment" => comment = Some(value.to_string()),
+                "column" => {
+                    column =
+                        Some(value.parse().map_err(|_| {
+                            format!("Invalid column: {}", value)
+                        })?)
+                }
+                _ => return Err(format!("Unknown key: {}", key)),
+            }
+        }
+        let id = id.ok_or_else(|| String::from("Missing id"))?;
+        let url =
+            url.ok_or_else(|| String::from("Missing url"))?;
+        Self::new(id, url, name, comment, column)
+    }
+
+    pub fn new(
+        id: i16,
+        url: String,
+        name: Option<String>,
+        comment: Option<String>,
+        column: Option<usize>,
+    ) -> Result<Self, String> {
+        if id < FEED_OFFSET {
+            return Err(format!(
+                "Id must be at least {}, lower ids are reserved",
+                FEED_OFFSET
+            ));
+        }
+        Ok(UrlList {
+            name: name.unwrap_or_else(|| url.clone()),
+            url,
+            comment: comment.unwrap_or_else(|| String::from("#")),
+            column,
+            backend_type: BackendType::Feed(id),
+        })
+    }
+
+    fn parse_line<'a>(&self, line: &'a str) -> Option<&'a str> {
+        let line = match line.find(&self.comment[..]) {
+            Some(pos) if !self.comment.is_empty() => &line[..pos],
+            _ => line,
+        };
+        match self.column {
+            Some(column) => line.split_whitespace().nth(column),
+            None => Some(line.trim()),
+        }
+        .filter(|value| !value.is_empty())
+    }
+}
+
+impl Provider for UrlList {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn backend_type(&self) -> BackendType {
+        self.backend_type
+    }
+
+    fn fetch(&self, client: &Client) -> Option<Vec<Blacklist>> {
+        let response = fetch_text(client, &self.name, &self.url)?;
+        Some(
+            response
+                .lines()
+                .filter_map(|line| self.parse_line(line))
+                .filter_map(|value| {
+                    Blacklist::new(value, self.backend_type)
+                })
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,352 @@
+use crate::{
+    args::{AdminCommand, CliArguments},
+    backend::store::{remove_network, store_manual},
+    db_op,
+    execute::create_database_connection,
+    helper::{convert_to_raw, parse_network},
+    middleware::diesel::{DBType, DieselPooledConnection},
+    routes::stats::count_per_source_query,
+    schema::{
+        allowlist, blacklist::dsl::blacklist, blacklist_source,
+        rate_limit,
+    },
+    types::{
+        allowlist::{is_allowlisted, Allowlist},
+        backend_type::BackendType,
+        blacklist::Blacklist,
+        check::{BlacklistIndex, CheckResult, SourceIndex},
+        ip_type::IpType,
+        rate_limit::RateLimit,
+        source::Source,
+        stats::IpsPerSource,
+    },
+};
+use diesel::{QueryDsl, RunQueryDsl};
+use log::{error, info};
+use std::{any::Any, net::IpAddr, process::exit};
+
+pub fn execute(args: &CliArguments, command: &AdminCommand) {
+    match args.db_type {
+        DBType::POSTGRES => {
+            run::<diesel::pg::PgConnection>(args, command)
+        }
+        DBType::MYSQL => {
+            run::<diesel::mysql::MysqlConnection>(args, command)
+        }
+        DBType::SQLITE => {
+            run::<diesel::sqlite::SqliteConnection>(args, command)
+        }
+    }
+}
+
+fn run<T: 'static + diesel::Connection>(
+    args: &CliArguments,
+    command: &AdminCommand,
+) {
+    let pool = create_database_connection::<T>(args).pool;
+    let db_conn: DieselPooledConnection<T> = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            error!("Unable to connect to database: {}", err);
+            exit(4);
+        }
+    };
+    match command {
+        AdminCommand::List => list(&db_conn, args.db_type),
+        AdminCommand::Add {
+            network,
+            ttl,
+            reason,
+        } => add(&db_conn, args.db_type, network, *ttl, reason),
+        AdminCommand::Remove { network } => {
+            remove(&db_conn, args.db_type, network)
+        }
+        AdminCommand::Check { address } => {
+            check(&db_conn, args.db_type, address)
+        }
+        AdminCommand::Stats => stats(&db_conn, args.db_type),
+        AdminCommand::Fsck => fsck(&db_conn, args.db_type),
+    }
+}
+
+fn list(db_conn: &dyn Any, db_type: DBType) {
+    let values = db_op!(db_conn, db_type, conn {
+        blacklist.load::<Blacklist>(conn)
+    });
+    match values {
+        Ok(values) => {
+            for value in values {
+                println!(
+                    "{} {} {} {}",
+                    value
+                        .to_plain()
+                        .unwrap_or_else(|| "-".into()),
+                    value.backend_type,
+                    value.last_update,
+                    value.reason.as_deref().unwrap_or("-"),
+                );
+            }
+        }
+        Err(err) => {
+            error!("Unable to list entries: {}", err);
+            exit(8);
+        }
+    }
+}
+
+/// Adds a manual entry, replacing an existing entry of the same
+/// network.
+fn add(
+    db_conn: &dyn Any,
+    db_type: DBType,
+    network: &str,
+    ttl: Option<u32>,
+    reason: &Option<String>,
+) {
+    let mut entry = match parse_network(network).and_then(
+        |(address, length)| {
+            Blacklist::new_network(
+                address,
+                length,
+                BackendType::Manual,
+                reason.clone(),
+            )
+        },
+    ) {
+        Some(entry) => entry,
+        None => {
+            error!("Invalid ip or network \"{}\"", network);
+            exit(1);
+        }
+    };
+    entry.expires = ttl.map(|ttl| {
+        entry.last_update
+            + chrono::Duration::seconds(i64::from(ttl))
+    });
+    let result = store_manual(db_conn, db_type, &entry);
+    if let Err(err) = result {
+        error!("Unable to add {}: {}", network, err);
+        exit(8);
+    }
+    info!(
+        "Added {} to the blacklist",
+        entry.to_plain().unwrap_or_default()
+    );
+}
+
+/// Removes every entry of the given network, regardless of the
+/// provider.
+fn remove(db_conn: &dyn Any, db_type: DBType, network: &str) {
+    let (raw_ip, raw_type, length) = match parse_network(network)
+    {
+        Some((address, length)) => {
+            let (raw_ip, raw_type) =
+                convert_to_raw(address, length);
+            (raw_ip, raw_type, i16::from(length))
+        }
+        None => {
+            error!("Invalid ip or network \"{}\"", network);
+            exit(1);
+        }
+    };
+    let result = remove_network(
+        db_conn, db_type, &raw_ip, raw_type, length,
+    );
+    match result {
+        Ok(0) => {
+            error!("{} is not listed", network);
+            exit(8);
+        }
+        Ok(_) => info!("Removed {} from the blacklist", network),
+        Err(err) => {
+            error!("Unable to remove {}: {}", network, err);
+            exit(8);
+        }
+    }
+}
+
+/// Prints every entry listing the given address. Entries hidden by the
+/// allowlist are marked as such.
+fn check(db_conn: &dyn Any, db_type: DBType, address: &str) {
+    let parsed: IpAddr = match address.parse() {
+        Ok(parsed) => parsed,
+        Err(_) => {
+            error!("Invalid ip \"{}\"", address);
+            exit(1);
+        }
+    };
+    let values = db_op!(db_conn, db_type, conn {
+        allowlist::table
+            .load::<Allowlist>(conn)
+            .and_then(|allowed| {
+                blacklist
+                    .load::<Blacklist>(conn)
+                    .map(|values| (allowed, values))
+            })
+            .and_then(|(allowed, values)| {
+                blacklist_source::table
+                    .load::<Source>(conn)
+                    .map(|sources| (allowed, values, sources))
+            })
+    });
+    let (allowed, values, sources) = match values {
+        Ok(values) => values,
+        Err(err) => {
+            error!("Unable to load entries: {}", err);
+            exit(8);
+        }
+    };
+    let index = BlacklistIndex::new(values.iter());
+    let sources = SourceIndex::new(sources);
+    let (hidden, listed): (Vec<&Blacklist>, Vec<&Blacklist>) =
+        index
+            .lookup(parsed)
+            .into_iter()
+            .partition(|value| is_allowlisted(&allowed, value));
+    if listed.is_empty() {
+        println!("{} is not listed", parsed);
+    } else {
+        let result = CheckResult {
+            ip: parsed.to_string(),
+            entries: listed
+                .into_iter()
+                .flat_map(|value| sources.entries(value))
+                .collect(),
+        };
+        println!("{}", result.to_plain());
+    }
+    for value in hidden {
+        println!(
+            "{} {} {} allowlisted",
+            parsed,
+            value.to_plain().unwrap_or_else(|| "-".into()),
+            value.backend_type,
+        );
+    }
+}
+
+fn stats(db_conn: &dyn Any, db_type: DBType) {
+    let values = db_op!(db_conn, db_type, conn {
+        blacklist.count().get_result::<i64>(conn).and_then(|count| {
+            count_per_source_query()
+                .load::<IpsPerSource>(conn)
+                .map(|sources| (count, sources))
+        })
+        .and_then(|(count, sources)| {
+            rate_limit::table
+                .load::<RateLimit>(conn)
+                .map(|limits| (count, sources, limits))
+        })
+    });
+    match values {
+        Ok((count, sources, limits)) => {
+            println!("total {}", count);
+            for source in sources {
+                println!(
+                    "{} {}",
+                    source.backend_type, source.count
+                );
+            }
+            for limit in limits {
+                println!("rate-limit {}", limit.to_plain());
+            }
+        }
+        Err(err) => {
+            error!("Unable to load stats: {}", err);
+            exit(8);
+        }
+    }
+}
+
+/// Reports every stored network whose raw address does not match its
+/// ip type or prefix length. Such rows are silently skipped when the
+/// blacklist is served.
+fn fsck(db_conn: &dyn Any, db_type: DBType) {
+    let values = db_op!(db_conn, db_type, conn {
+        blacklist.load::<Blacklist>(conn).and_then(|values| {
+            blacklist_source::table
+                .load::<Source>(conn)
+                .and_then(|sources| {
+                    allowlist::table
+                        .load::<Allowlist>(conn)
+                        .map(|allowed| (values, sources, allowed))
+                })
+        })
+    });
+    let (values, sources, allowed) = match values {
+        Ok(values) => values,
+        Err(err) => {
+            error!("Unable to load entries: {}", err);
+            exit(8);
+        }
+    };
+    let rows = values
+        .iter()
+        .map(|value| {
+            ("blacklist", &value.ip, value.ip_type, value.prefix)
+        })
+        .chain(sources.iter().map(|source| {
+            (
+                "blacklist_source",
+                &source.ip,
+                source.ip_type,
+                source.prefix,
+            )
+        }))
+        .chain(allowed.iter().map(|allowed| {
+            (
+                "allowlist",
+                &allowed.ip,
+                allowed.ip_type,
+                allowed.prefix,
+            )
+        }));
+    let mut problems = 0;
+    for (table, raw_ip, raw_type, length) in rows {
+        if let Some(problem) = check_row(raw_ip, raw_type, length)
+        {
+            problems += 1;
+            println!(
+                "{} ip={} ip_type={} prefix={}: {}",
+                table,
+                raw_ip
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect::<String>(),
+                i16::from(raw_type),
+                length,
+                problem
+            );
+        }
+    }
+    if problems > 0 {
+        error!("Found {} invalid rows", problems);
+        exit(8);
+    }
+}
+
+/// Describes why the given network can not be decoded, if it can not.
+fn check_row(
+    raw_ip: &[u8],
+    raw_type: IpType,
+    length: i16,
+) -> Option<String> {
+    let (expected, max_prefix) = match raw_type {
+        IpType::Ipv4 => (4, 32),
+        IpType::Ipv6 => (16, 128),
+        IpType::Unknown(n) => {
+            return Some(format!("unknown ip type {}", n))
+        }
+    };
+    if raw_ip.len() != expected {
+        Some(format!(
+            "{} bytes, expected {}",
+            raw_ip.len(),
+            expected
+        ))
+    } else if length < 0 || length > max_prefix {
+        Some(format!("invalid prefix length {}", length))
+    } else {
+        None
+    }
+}
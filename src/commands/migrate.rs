@@ -0,0 +1,54 @@
+use crate::{
+    args::CliArguments,
+    execute::create_database_connection,
+    middleware::diesel::{DBType, DieselPooledConnection},
+    migrations,
+};
+use log::error;
+use std::{io, process::exit};
+
+pub fn execute(args: &CliArguments, dry_run: bool) {
+    match args.db_type {
+        DBType::POSTGRES => {
+            run::<diesel::pg::PgConnection>(args, dry_run)
+        }
+        DBType::MYSQL => {
+            run::<diesel::mysql::MysqlConnection>(args, dry_run)
+        }
+        DBType::SQLITE => {
+            run::<diesel::sqlite::SqliteConnection>(args, dry_run)
+        }
+    }
+}
+
+fn run<T: 'static + diesel::Connection>(
+    args: &CliArguments,
+    dry_run: bool,
+) {
+    let pool = create_database_connection::<T>(args).pool;
+    let db_conn: DieselPooledConnection<T> = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            error!("Unable to connect to database: {}", err);
+            exit(4);
+        }
+    };
+    if dry_run {
+        match migrations::pending(&db_conn, args.db_type) {
+            Ok(versions) => {
+                for version in versions {
+                    println!("Pending migration {}", version);
+                }
+            }
+            Err(err) => {
+                error!("Unable to load migrations: {}", err);
+                exit(6);
+            }
+        }
+    } else if let Err(err) =
+        migrations::run(&db_conn, args.db_type, &mut io::stdout())
+    {
+        error!("Unable to run migrations: {}", err);
+        exit(6);
+    }
+}
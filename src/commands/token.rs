@@ -0,0 +1,114 @@
+use crate::{
+    args::{CliArguments, TokenCommand},
+    db_op,
+    execute::{create_database_connection, migrate_database},
+    middleware::diesel::{DBType, DieselPooledConnection},
+    schema::api_token::dsl::*,
+    types::api_token::{ApiToken, TokenScope},
+};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use log::error;
+use std::{any::Any, process::exit};
+
+pub fn execute(args: &CliArguments, command: &TokenCommand) {
+    match args.db_type {
+        DBType::POSTGRES => {
+            run::<diesel::pg::PgConnection>(args, command)
+        }
+        DBType::MYSQL => {
+            run::<diesel::mysql::MysqlConnection>(args, command)
+        }
+        DBType::SQLITE => {
+            run::<diesel::sqlite::SqliteConnection>(args, command)
+        }
+    }
+}
+
+fn run<T: 'static + diesel::Connection>(
+    args: &CliArguments,
+    command: &TokenCommand,
+) {
+    let pool = create_database_connection::<T>(args).pool;
+    migrate_database(args, &pool);
+    let db_conn: DieselPooledConnection<T> = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            error!("Unable to connect to database: {}", err);
+            exit(4);
+        }
+    };
+    match command {
+        TokenCommand::Create {
+            name: token_name,
+            scope: token_scope,
+        } => create(
+            &db_conn,
+            args.db_type,
+            token_name,
+            *token_scope,
+        ),
+        TokenCommand::Revoke { name: token_name } => {
+            revoke(&db_conn, args.db_type, token_name)
+        }
+        TokenCommand::List => list(&db_conn, args.db_type),
+    }
+}
+
+fn create(
+    db_conn: &dyn Any,
+    db_type: DBType,
+    token_name: &str,
+    token_scope: TokenScope,
+) {
+    let (entry, token) =
+        ApiToken::generate(token_name, token_scope);
+    let inserted = db_op!(db_conn, db_type, conn {
+        diesel::insert_into(api_token).values(&entry).execute(conn)
+    });
+    if let Err(err) = inserted {
+        error!("Unable to create token {}: {}", token_name, err);
+        exit(5);
+    }
+    println!("{}", token);
+}
+
+fn revoke(db_conn: &dyn Any, db_type: DBType, token_name: &str) {
+    let deleted = db_op!(db_conn, db_type, conn {
+        diesel::delete(api_token.filter(name.eq(token_name)))
+            .execute(conn)
+    });
+    match deleted {
+        Ok(0) => {
+            error!("Token {} does not exist", token_name);
+            exit(5);
+        }
+        Ok(_) => {}
+        Err(err) => {
+            error!(
+                "Unable to revoke token {}: {}",
+                token_name, err
+            );
+            exit(5);
+        }
+    }
+}
+
+fn list(db_conn: &dyn Any, db_type: DBType) {
+    let values = db_op!(db_conn, db_type, conn {
+        api_token.order_by(name).load::<ApiToken>(conn)
+    });
+    match values {
+        Ok(values) => {
+            for value in values {
+                println!(
+                    "{} {} {}",
+                    value.name, value.scope, value.created
+                );
+            }
+        }
+        Err(err) => {
+            error!("Unable to list tokens: {}", err);
+            exit(5);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{
+    args::CliArguments,
+    backend::{
+        provider::{providers, Provider},
+        store::{update_provider, UpdateSummary},
+    },
+    execute::{
+        apply_allowlist, create_database_connection,
+        delete_old_ips, migrate_database,
+    },
+    middleware::diesel::{DBType, DieselPooledConnection},
+};
+use log::error;
+use reqwest::blocking::Client;
+use std::process::exit;
+
+pub fn execute(args: &CliArguments, names: &[String]) {
+    match args.db_type {
+        DBType::POSTGRES => {
+            run::<diesel::pg::PgConnection>(args, names)
+        }
+        DBType::MYSQL => {
+            run::<diesel::mysql::MysqlConnection>(args, names)
+        }
+        DBType::SQLITE => {
+            run::<diesel::sqlite::SqliteConnection>(args, names)
+        }
+    }
+}
+
+fn run<T: 'static + diesel::Connection>(
+    args: &CliArguments,
+    names: &[String],
+) {
+    let selected = select_providers(args, names);
+    let pool = create_database_connection::<T>(args).pool;
+    migrate_database(args, &pool);
+    apply_allowlist(args, &pool);
+    let db_conn: DieselPooledConnection<T> = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            error!("Unable to connect to database: {}", err);
+            exit(4);
+        }
+    };
+
+    let client = Client::new();
+    let mut summary = UpdateSummary::default();
+    for provider in selected {
+        summary += update_provider(
+            args,
+            provider.as_ref(),
+            &db_conn,
+            args.db_type,
+            &client,
+            true,
+        );
+    }
+    summary += delete_old_ips(args, &db_conn, args.db_type);
+    println!("{}", summary);
+    if summary.errors > 0 {
+        exit(7);
+    }
+}
+
+/// The providers named on the command line, every provider if none
+/// was given. Exits on unknown names.
+fn select_providers(
+    args: &CliArguments,
+    names: &[String],
+) -> Vec<Box<dyn Provider>> {
+    let available = providers(args);
+    if names.is_empty() {
+        return available;
+    }
+    for name in names {
+        if !available.iter().any(|provider| {
+            provider.backend_type().to_string() == *name
+        }) {
+            error!(
+                "Unknown provider \"{}\", configured are: {}",
+                name,
+                available
+                    .iter()
+                    .map(|provider| provider
+                        .backend_type()
+                        .to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            );
+            exit(1);
+        }
+    }
+    available
+        .into_iter()
+        .filter(|provider| {
+            names.contains(&provider.backend_type().to_string())
+        })
+        .collect()
+}
@@ -1,80 +1,145 @@
 use crate::{
     args::CliArguments,
+    backend::{
+        abuseipdb::AbuseIpDb,
+        provider::{providers, Provider},
+        schedule::{ReportQueue, SchedulerState},
+        store::{
+            delete_unsourced, insert_history, load_rate_limits,
+            recheck_provider, store_allowlist, store_rate_limits,
+            update_provider, UpdateSummary,
+        },
+    },
     db_op,
     middleware::{
+        auth::Auth,
         diesel::{
             DBType, DieselMiddleware, DieselPool,
-            DieselPooledConnection,
+            DieselPooledConnection, PoolConfig,
         },
         logger::Logger,
+        schedule::ScheduleMiddleware,
     },
+    migrations,
     routes::{api, stats},
-    schema::blacklist::dsl::*,
-    types::blacklist::Blacklist,
+    schema::{
+        blacklist_change, blacklist_history, blacklist_source,
+    },
+    types::{
+        backend_type::BackendType,
+        history::{HistoryEvent, NewHistory},
+        report::Report,
+        source::Source,
+    },
 };
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 use clokwerk::{ScheduleHandle, Scheduler, TimeUnits};
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods,
+    QueryDsl, RunQueryDsl,
+};
 use iron::{status, Chain, Iron, IronResult, Request, Response};
-use log::{debug, error, info, trace};
+use log::{debug, error, info, trace, warn};
 use reqwest::blocking::Client;
-use std::{any::Any, process::exit, time::Duration};
+use std::{
+    any::Any, collections::VecDeque, process::exit,
+    time::Duration,
+};
+
+/// Minutes between two batches of rechecks.
+const RECHECK_INTERVAL_MINUTES: u32 = 5;
+/// Seconds between two runs forwarding the queued reports.
+const REPORT_INTERVAL_SECONDS: u32 = 10;
+/// Minutes between two sweeps of expired entries.
+const EXPIRY_INTERVAL_MINUTES: u32 = 1;
 
 fn request(req: &mut Request) -> IronResult<Response> {
     trace!("Handling request: {:?}", req);
-    #[allow(clippy::map_clone)]
-    match req.url.path().get(0).map(|&t| t).unwrap_or("") {
+    match req.url.path().first().copied().unwrap_or("") {
         "api" => api::index(req),
         "stats" => stats::index(req),
         _ => Ok(Response::with(status::NotFound)),
     }
 }
 
+/// The url used to connect to the configured database. Sqlite uses
+/// the database path instead. A given `--db-url` supersedes the
+/// individual fields.
+fn database_url(args: &CliArguments) -> String {
+    if let Some(url) = &args.db_url {
+        return url.clone();
+    }
+    match args.db_type {
+        DBType::SQLITE => args.db_path.clone(),
+        _ => format!(
+            "{}://{}:{}@{}:{}/{}",
+            args.db_type,
+            args.db_user,
+            args.db_pass,
+            args.db_host,
+            args.db_port,
+            args.db_name
+        ),
+    }
+}
+
 fn setup_diesel_and_schedular(
     args: &CliArguments,
     chain: &mut Chain,
+    state: SchedulerState,
 ) -> ScheduleHandle {
-    let url = format!(
-        "{}://{}:{}@{}:{}/{}",
-        args.db_type,
-        args.db_user,
-        args.db_pass,
-        args.db_host,
-        args.db_port,
-        args.db_name
-    );
     match args.db_type {
         DBType::POSTGRES => {
             let conn = create_database_connection::<
                 diesel::pg::PgConnection,
-            >(&url, args.db_type);
+            >(args);
             let pool = conn.pool.clone();
+            migrate_database(args, &pool);
+            apply_allowlist(args, &pool);
             chain.link_before(conn);
-            setup_scheduler(args, pool, args.db_type)
+            setup_scheduler(args, pool, args.db_type, state)
         }
         DBType::MYSQL => {
             let conn = create_database_connection::<
                 diesel::mysql::MysqlConnection,
-            >(&url, args.db_type);
+            >(args);
             let pool = conn.pool.clone();
+            migrate_database(args, &pool);
+            apply_allowlist(args, &pool);
             chain.link_before(conn);
-            setup_scheduler(args, pool, args.db_type)
+            setup_scheduler(args, pool, args.db_type, state)
         }
         DBType::SQLITE => {
             let conn = create_database_connection::<
                 diesel::sqlite::SqliteConnection,
-            >(&args.db_path, args.db_type);
+            >(args);
             let pool = conn.pool.clone();
+            migrate_database(args, &pool);
+            apply_allowlist(args, &pool);
             chain.link_before(conn);
-            setup_scheduler(args, pool, args.db_type)
+            setup_scheduler(args, pool, args.db_type, state)
         }
     }
 }
 
-fn create_database_connection<T: diesel::Connection>(
-    url: &str,
-    db_type: DBType,
+pub fn create_database_connection<T: diesel::Connection>(
+    args: &CliArguments,
 ) -> DieselMiddleware<T> {
-    match DieselMiddleware::new(url, db_type) {
+    let pool_config = PoolConfig {
+        max_size: args.db_pool_size,
+        connection_timeout: Duration::from_secs(u64::from(
+            args.db_connection_timeout,
+        )),
+        idle_timeout: match args.db_idle_timeout {
+            0 => None,
+            secs => Some(Duration::from_secs(u64::from(secs))),
+        },
+    };
+    match DieselMiddleware::new(
+        &database_url(args),
+        args.db_type,
+        pool_config,
+    ) {
         Err(err) => {
             error!("Unable to connect to database: {}", err);
             exit(4);
@@ -83,74 +148,421 @@ fn create_database_connection<T: diesel::Connection>(
     }
 }
 
+/// Applies pending migrations unless disabled and refuses to start if
+/// the schema is still behind the binary.
+pub fn migrate_database<T: 'static + diesel::Connection>(
+    args: &CliArguments,
+    db_pool: &DieselPool<T>,
+) {
+    let db_conn: DieselPooledConnection<T> = match db_pool.get() {
+        Ok(conn) => conn,
+        Err(err) => {
+            error!("Unable to connect to database: {}", err);
+            exit(4);
+        }
+    };
+    if args.auto_migrate {
+        let mut output = Vec::new();
+        let result =
+            migrations::run(&db_conn, args.db_type, &mut output);
+        for line in String::from_utf8_lossy(&output).lines() {
+            info!("{}", line);
+        }
+        if let Err(err) = result {
+            error!("Unable to run migrations: {}", err);
+            exit(6);
+        }
+    }
+    match migrations::pending(&db_conn, args.db_type) {
+        Ok(ref versions) if versions.is_empty() => {}
+        Ok(versions) => {
+            error!(
+                "Database schema is behind, pending migrations: {}. Run `blacklistd migrate` to apply them",
+                versions.join(", ")
+            );
+            exit(6);
+        }
+        Err(err) => {
+            error!("Unable to load migrations: {}", err);
+            exit(6);
+        }
+    }
+}
+
+/// Adds the allowlist entries of the config file.
+pub fn apply_allowlist<T: 'static + diesel::Connection>(
+    args: &CliArguments,
+    db_pool: &DieselPool<T>,
+) {
+    if args.allowlist.is_empty() {
+        return;
+    }
+    match db_pool.get() {
+        Ok(db_conn) => {
+            if let Err(err) = store_allowlist(
+                &db_conn,
+                args.db_type,
+                &args.allowlist,
+            ) {
+                error!(
+                    "Unable to store allowlist with: {:#?}",
+                    err
+                );
+            }
+        }
+        Err(err) => {
+            error!("Unable to connect to database: {}", err);
+            exit(4);
+        }
+    }
+}
+
 fn setup_scheduler<T: 'static + diesel::Connection>(
     args: &CliArguments,
     db_pool: DieselPool<T>,
     db_type: DBType,
+    state: SchedulerState,
 ) -> ScheduleHandle {
+    let now = Utc::now();
     if let Some(db_conn) = db_pool.try_get() {
-        let db_conn: &dyn Any = &db_conn;
-        let update_necessary: bool = db_op!(db_conn, db_type, conn {
-            let stale_threshold = chrono::Utc::now()
-                .checked_sub_signed(chrono::Duration::days(1))
-                .expect("Unable to substract 1 day")
-                .naive_utc();
-            let values = blacklist
-                .order_by(last_update.desc())
-                .limit(1)
-                .load::<Blacklist>(conn)
-                .ok().and_then(|mut v| v.pop());
-            match values {
-                Some(ref value) if value.last_update > stale_threshold => false,
-                _ => true
-            }
-        });
-        if update_necessary {
-            update_blacklists(&args, db_pool.clone(), db_type);
+        for provider in providers(args) {
+            let provider_type = provider.backend_type();
+            let schedule = args.schedule_of(provider_type);
+            let next_run = match last_provider_update(
+                &db_conn,
+                db_type,
+                provider_type,
+            ) {
+                Some(last) if schedule.next_after(last) > now => {
+                    schedule.next_after_with_jitter(
+                        now,
+                        args.schedule_jitter,
+                    )
+                }
+                _ => now,
+            };
+            state.next_runs.plan(
+                provider_type,
+                provider.name(),
+                next_run,
+            );
         }
     } else {
         error!("Connection to database lost");
     }
+    update_blacklists(args, db_pool.clone(), db_type, &state);
 
-    let args = args.clone();
+    // Due providers are checked often to start queued runs early
     let mut scheduler = Scheduler::new();
-    scheduler.every(1.day()).at("12:00 am").run(move || {
-        update_blacklists(&args, db_pool.clone(), db_type)
+    if let Some(key) = &args.api_abuseipdb {
+        let abuseipdb =
+            AbuseIpDb::new(key, args.abuseipdb_query.clone());
+        let reports = state.reports.clone();
+        let report_pool = db_pool.clone();
+        scheduler.every(REPORT_INTERVAL_SECONDS.seconds()).run(
+            move || {
+                forward_reports(
+                    &abuseipdb,
+                    &reports,
+                    &report_pool,
+                    db_type,
+                )
+            },
+        );
+    }
+    let update_args = args.clone();
+    let update_pool = db_pool.clone();
+    scheduler.every(10.seconds()).run(move || {
+        update_blacklists(
+            &update_args,
+            update_pool.clone(),
+            db_type,
+            &state,
+        )
     });
-    scheduler.watch_thread(Duration::from_secs(60))
+    let expiry_pool = db_pool.clone();
+    scheduler.every(EXPIRY_INTERVAL_MINUTES.minutes()).run(
+        move || expire_blacklists(expiry_pool.clone(), db_type),
+    );
+    let args = args.clone();
+    scheduler.every(RECHECK_INTERVAL_MINUTES.minutes()).run(
+        move || {
+            recheck_blacklists(&args, db_pool.clone(), db_type)
+        },
+    );
+    scheduler.watch_thread(Duration::from_secs(1))
 }
 
+/// The time the given provider last listed an entry.
+fn last_provider_update(
+    db_conn: &dyn Any,
+    db_type: DBType,
+    provider_type: BackendType,
+) -> Option<DateTime<Utc>> {
+    db_op!(db_conn, db_type, conn {
+        blacklist_source::table
+            .select(blacklist_source::last_update)
+            .filter(blacklist_source::backend_type.eq(provider_type))
+            .order_by(blacklist_source::last_update.desc())
+            .limit(1)
+            .load::<NaiveDateTime>(conn)
+            .map_err(|err| {
+                error!("Unable to load last update with: {:#?}", err)
+            })
+            .ok()
+            .and_then(|mut values| values.pop())
+    })
+    .map(|time| Utc.from_utc_datetime(&time))
+}
+
+/// Updates every provider which is due and plans its next run.
 fn update_blacklists<T: 'static + diesel::Connection>(
     args: &CliArguments,
     db_pool: DieselPool<T>,
     db_type: DBType,
+    state: &SchedulerState,
 ) {
+    let now = Utc::now();
+    let due: Vec<Box<dyn Provider>> = providers(args)
+        .into_iter()
+        .filter(|provider| {
+            state.next_runs.is_due(provider.backend_type(), now)
+        })
+        .collect();
+    if due.is_empty() {
+        return;
+    }
     info!("Updating blacklist");
+    state.update_runs.start(
+        due.iter()
+            .map(|provider| provider.backend_type())
+            .collect(),
+    );
 
     let client = Client::new();
+    let mut summary = UpdateSummary::default();
     let db_conn: DieselPooledConnection<T> =
         match db_pool.try_get() {
             Some(conn) => conn,
             None => {
                 error!("Connection to database lost");
+                summary.errors += 1;
+                state.update_runs.finish(summary);
                 return;
             }
         };
 
-    if args.api_abuseipdb.is_some() {
-        crate::backend::abuseipdb::update_abuseipdb(
-            args, &db_conn, db_type, &client,
+    for provider in due {
+        summary += update_provider(
+            args,
+            provider.as_ref(),
+            &db_conn,
+            db_type,
+            &client,
+            false,
+        );
+        let provider_type = provider.backend_type();
+        state.next_runs.plan(
+            provider_type,
+            provider.name(),
+            args.schedule_of(provider_type)
+                .next_after_with_jitter(
+                    Utc::now(),
+                    args.schedule_jitter,
+                ),
         );
     }
     debug!("Deleting stale ips");
-    delete_old_ips(args, &db_conn, db_type);
+    summary += delete_old_ips(args, &db_conn, db_type);
+    info!("Update completed. {}", summary);
+    state.update_runs.finish(summary);
+}
+
+/// Removes expired entries independent of the provider updates.
+fn expire_blacklists<T: 'static + diesel::Connection>(
+    db_pool: DieselPool<T>,
+    db_type: DBType,
+) {
+    let db_conn: DieselPooledConnection<T> =
+        match db_pool.try_get() {
+            Some(conn) => conn,
+            None => {
+                error!("Connection to database lost");
+                return;
+            }
+        };
+    let summary = delete_expired_ips(&db_conn, db_type);
+    if summary.deleted > 0 || summary.errors > 0 {
+        info!("Expiry completed. {}", summary);
+    }
 }
 
-fn delete_old_ips(
+/// Rechecks the next batch of old entries of every provider which
+/// supports single checks.
+fn recheck_blacklists<T: 'static + diesel::Connection>(
     args: &CliArguments,
-    db_conn: &dyn Any,
+    db_pool: DieselPool<T>,
     db_type: DBType,
 ) {
+    let db_conn: DieselPooledConnection<T> =
+        match db_pool.try_get() {
+            Some(conn) => conn,
+            None => {
+                error!("Connection to database lost");
+                return;
+            }
+        };
+    let client = Client::new();
+    let interval = chrono::Duration::minutes(i64::from(
+        RECHECK_INTERVAL_MINUTES,
+    ));
+    for provider in providers(args) {
+        if !provider.supports_check() {
+            continue;
+        }
+        let summary = recheck_provider(
+            args,
+            provider.as_ref(),
+            &db_conn,
+            db_type,
+            &client,
+            interval,
+        );
+        debug!(
+            "Recheck of {} completed. {}",
+            provider.name(),
+            summary
+        );
+    }
+}
+
+/// Forwards the queued reports to AbuseIPDB. Reports stay queued while
+/// the report budget is exhausted. The budget is shared with the
+/// provider using the database, so it survives restarts.
+fn forward_reports<T: 'static + diesel::Connection>(
+    abuseipdb: &AbuseIpDb,
+    reports: &ReportQueue,
+    db_pool: &DieselPool<T>,
+    db_type: DBType,
+) {
+    let mut pending: VecDeque<Report> = reports.drain().into();
+    if pending.is_empty() {
+        return;
+    }
+    let db_conn: DieselPooledConnection<T> =
+        match db_pool.try_get() {
+            Some(conn) => conn,
+            None => {
+                error!("Connection to database lost");
+                reports.requeue(pending.into_iter().collect());
+                return;
+            }
+        };
+    abuseipdb.restore_rate_limits(load_rate_limits(
+        &db_conn,
+        db_type,
+        abuseipdb.backend_type(),
+    ));
+    let client = Client::new();
+    let mut forwarded = 0;
+    let mut failed = 0;
+    while let Some(report) = pending.pop_front() {
+        if let Some(reset) = abuseipdb.reports_exhausted_until() {
+            pending.push_front(report);
+            warn!(
+                "Delaying {} abuseipdb reports, report requests exhausted until {}",
+                pending.len(),
+                reset
+            );
+            break;
+        }
+        match abuseipdb.report(&client, &report) {
+            Some(()) => {
+                reports.mark_reported(
+                    report.ip,
+                    chrono::Utc::now().naive_utc(),
+                );
+                forwarded += 1
+            }
+            None if abuseipdb
+                .reports_exhausted_until()
+                .is_some() =>
+            {
+                pending.push_front(report)
+            }
+            None => {
+                reports.release(report.ip);
+                failed += 1
+            }
+        }
+    }
+    reports.requeue(pending.into_iter().collect());
+    store_rate_limits(
+        &db_conn,
+        db_type,
+        &abuseipdb.rate_limits(),
+    );
+    info!(
+        "Reports forwarded. {} Reported, {} Failed",
+        forwarded, failed
+    );
+}
+
+/// Removes expired sources and the entries no longer listed by any
+/// other source.
+pub fn delete_expired_ips(
+    db_conn: &dyn Any,
+    db_type: DBType,
+) -> UpdateSummary {
+    let mut summary = UpdateSummary::default();
+    let now = chrono::Utc::now().naive_utc();
+    let expired_filter = blacklist_source::expires.lt(now);
+    let deleted_sources = db_op!(db_conn, db_type, conn {
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let expired = blacklist_source::table
+                .filter(expired_filter)
+                .load::<Source>(conn)?;
+            if expired.is_empty() {
+                return Ok(0);
+            }
+            let deleted =
+                diesel::delete(blacklist_source::table.filter(expired_filter))
+                    .execute(conn)?;
+            let events: Vec<NewHistory> = expired
+                .iter()
+                .map(|source| {
+                    NewHistory::new(source, HistoryEvent::Expired, None)
+                })
+                .collect();
+            insert_history(db_conn, db_type, &events)?;
+            Ok(deleted)
+        })
+    })
+    .unwrap_or_else(|err| {
+        summary.errors += 1;
+        error!("Unable to delete expired ips with: {:#?}", err);
+        0
+    });
+    if deleted_sources == 0 {
+        return summary;
+    }
+    let deleted = delete_unsourced(db_conn, db_type);
+    debug!(
+        "Expiry Completed. {} Sources, {} Ips Deleted",
+        deleted_sources, deleted
+    );
+    summary.deleted = deleted_sources;
+    summary
+}
+
+/// Removes stale and expired sources, entries without any source and
+/// changes and history beyond their retention.
+pub fn delete_old_ips(
+    args: &CliArguments,
+    db_conn: &dyn Any,
+    db_type: DBType,
+) -> UpdateSummary {
+    let mut summary = delete_expired_ips(db_conn, db_type);
     let removale_threshold = chrono::Utc::now()
         .checked_sub_signed(chrono::Duration::days(i64::from(
             args.stale_days,
@@ -158,25 +570,103 @@ fn delete_old_ips(
         .expect("Unable to substract days")
         .naive_utc();
 
-    let deleted = db_op!(db_conn, db_type, conn {
-        diesel::delete(
-                blacklist.filter(
-                    last_update
-                        .lt(removale_threshold),
-                ),
-            ).execute(conn) .map_err(|err| {
-                error!("Unable to delete ips with: {:#?}", err)
+    let stale_filter = blacklist_source::backend_type
+        .ne(BackendType::Manual)
+        .and(
+            blacklist_source::last_update.lt(removale_threshold),
+        );
+    let deleted_sources = db_op!(db_conn, db_type, conn {
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let stale = blacklist_source::table
+                .filter(stale_filter)
+                .load::<Source>(conn)?;
+            let deleted =
+                diesel::delete(blacklist_source::table.filter(stale_filter))
+                    .execute(conn)?;
+            let events: Vec<NewHistory> = stale
+                .iter()
+                .map(|source| {
+                    NewHistory::new(
+                        source,
+                        HistoryEvent::StaleDeleted,
+                        None,
+                    )
+                })
+                .collect();
+            insert_history(db_conn, db_type, &events)?;
+            Ok(deleted)
+        })
+    })
+    .unwrap_or_else(|err| {
+        summary.errors += 1;
+        error!("Unable to delete stale ips with: {:#?}", err);
+        0
+    });
+    let deleted = delete_unsourced(db_conn, db_type);
+    // The newest change is kept to detect expired cursors
+    let pruned = db_op!(db_conn, db_type, conn {
+        blacklist_change::table
+            .select(blacklist_change::id)
+            .order_by(blacklist_change::id.desc())
+            .limit(1)
+            .load::<i64>(conn)
+            .and_then(|mut newest| match newest.pop() {
+                Some(newest) => diesel::delete(
+                    blacklist_change::table.filter(
+                        blacklist_change::created
+                            .lt(removale_threshold)
+                            .and(blacklist_change::id.lt(newest)),
+                    ),
+                )
+                .execute(conn),
+                None => Ok(0),
+            })
+            .unwrap_or_else(|err| {
+                summary.errors += 1;
+                error!("Unable to prune changes with: {:#?}", err);
+                0
             })
-            .unwrap_or(0)
     });
-    debug!("Removale Completed. {} Deleted", deleted);
+    let history_threshold = chrono::Utc::now()
+        .checked_sub_signed(chrono::Duration::days(i64::from(
+            args.history_days,
+        )))
+        .expect("Unable to substract days")
+        .naive_utc();
+    let pruned_history = db_op!(db_conn, db_type, conn {
+        diesel::delete(
+            blacklist_history::table
+                .filter(blacklist_history::created.lt(history_threshold)),
+        )
+        .execute(conn)
+        .unwrap_or_else(|err| {
+            summary.errors += 1;
+            error!("Unable to prune history with: {:#?}", err);
+            0
+        })
+    });
+    debug!(
+        "Removale Completed. {} Sources, {} Ips, {} Changes, {} History Deleted",
+        deleted_sources, deleted, pruned, pruned_history
+    );
+    summary.deleted += deleted_sources;
+    summary
 }
 
 pub fn execute(args: &CliArguments) {
     let mut chain = Chain::new(request);
     let (logger_before, logger_after) = Logger::new();
     chain.link_before(logger_before);
-    let scheduler = setup_diesel_and_schedular(&args, &mut chain);
+    let state = SchedulerState {
+        reports: ReportQueue::new(args.api_abuseipdb.is_some()),
+        ..SchedulerState::default()
+    };
+    chain.link_before(ScheduleMiddleware {
+        state: state.clone(),
+    });
+    let scheduler =
+        setup_diesel_and_schedular(&args, &mut chain, state);
+    chain.link_before(Auth::new(args.anonymous_read));
 
     chain.link_after(logger_after);
 
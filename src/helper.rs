@@ -1,29 +1,198 @@
 use crate::types::ip_type::IpType;
 use iron::{
-    headers::Allow, method::Method, status, IronResult, Response,
+    headers::{Accept, Allow},
+    method::Method,
+    mime::{Mime, SubLevel, TopLevel},
+    status, IronResult, Request, Response,
+};
+use std::{
+    cmp::Reverse,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
 };
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use time::OffsetDateTime;
 
+/// Decodes a stored address. Returns `None` unless the address has
+/// exactly the length of its ip type.
 pub fn convert_ip(
     raw_ip: &[u8],
     ip_type: IpType,
 ) -> Option<IpAddr> {
     match ip_type {
-        IpType::Ipv4 if raw_ip.len() >= 4 => {
+        IpType::Ipv4 if raw_ip.len() == 4 => {
             let mut input = [0; 4];
-            input.copy_from_slice(&raw_ip[0..4]);
+            input.copy_from_slice(raw_ip);
             Some(IpAddr::V4(Ipv4Addr::from(input)))
         }
-        IpType::Ipv6 if raw_ip.len() >= 16 => {
+        IpType::Ipv6 if raw_ip.len() == 16 => {
             let mut input = [0; 16];
-            input.copy_from_slice(&raw_ip[0..16]);
+            input.copy_from_slice(raw_ip);
             Some(IpAddr::V6(Ipv6Addr::from(input)))
         }
         _ => None,
     }
 }
 
+/// Converts a stored network into its textual representation. Single
+/// hosts are returned without prefix length, e.g. `10.0.0.1` and
+/// `10.0.0.0/8`.
+pub fn convert_network(
+    raw_ip: &[u8],
+    ip_type: IpType,
+    prefix: i16,
+) -> Option<String> {
+    let address = convert_ip(raw_ip, ip_type)?;
+    let max_prefix = i16::from(max_prefix(&address));
+    if prefix < 0 || prefix > max_prefix {
+        None
+    } else if prefix == max_prefix {
+        Some(address.to_string())
+    } else {
+        Some(format!("{}/{}", address, prefix))
+    }
+}
+
+/// Parses an address with an optional prefix length, e.g. `10.0.0.1`
+/// or `10.0.0.0/8`. Addresses without prefix length cover a single
+/// host.
+pub fn parse_network(value: &str) -> Option<(IpAddr, u8)> {
+    let mut parts = value.trim().splitn(2, '/');
+    let address: IpAddr = parts.next()?.parse().ok()?;
+    let max_prefix = max_prefix(&address);
+    match parts.next() {
+        Some(prefix) => prefix
+            .parse()
+            .ok()
+            .filter(|prefix| *prefix <= max_prefix)
+            .map(|prefix| (address, prefix)),
+        None => Some((address, max_prefix)),
+    }
+}
+
+/// Converts a network into its stored representation. Host bits of
+/// the given address are cleared.
+pub fn convert_to_raw(
+    address: IpAddr,
+    prefix: u8,
+) -> (Vec<u8>, IpType) {
+    let (mut raw_ip, ip_type) = match address {
+        IpAddr::V4(addr) => {
+            (addr.octets().to_vec(), IpType::Ipv4)
+        }
+        IpAddr::V6(addr) => {
+            (addr.octets().to_vec(), IpType::Ipv6)
+        }
+    };
+    mask_network(&mut raw_ip, prefix);
+    (raw_ip, ip_type)
+}
+
+/// Every network containing the given address, from the address itself
+/// up to the whole address space.
+pub fn containing_networks(address: IpAddr) -> Vec<Vec<u8>> {
+    let mut networks: Vec<Vec<u8>> = (0..=max_prefix(&address))
+        .map(|prefix| convert_to_raw(address, prefix).0)
+        .collect();
+    networks.sort();
+    networks.dedup();
+    networks
+}
+
+/// Whether two stored networks share at least one address.
+pub fn networks_overlap(
+    (a, a_type, a_prefix): (&[u8], IpType, i16),
+    (b, b_type, b_prefix): (&[u8], IpType, i16),
+) -> bool {
+    if a_type != b_type || a.len() != b.len() {
+        return false;
+    }
+    let prefix = a_prefix.min(b_prefix).max(0) as usize;
+    a.iter().zip(b.iter()).enumerate().all(|(i, (a, b))| {
+        let start = i * 8;
+        if prefix <= start {
+            true
+        } else if prefix < start + 8 {
+            let mask = 0xff << (8 - (prefix - start));
+            a & mask == b & mask
+        } else {
+            a == b
+        }
+    })
+}
+
+/// Number of bits of the given address.
+pub fn max_prefix(address: &IpAddr) -> u8 {
+    match address {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+/// Clears every bit of the address after the given prefix length.
+fn mask_network(raw_ip: &mut [u8], prefix: u8) {
+    for (i, byte) in raw_ip.iter_mut().enumerate() {
+        let start = i * 8;
+        let prefix = usize::from(prefix);
+        if prefix <= start {
+            *byte = 0;
+        } else if prefix < start + 8 {
+            *byte &= 0xff << (8 - (prefix - start));
+        }
+    }
+}
+
+/// Parses the network given by the path segments starting at `index`,
+/// e.g. `/api/allowlist/10.0.0.0/8`.
+pub fn path_network(
+    req: &Request,
+    index: usize,
+) -> Option<(IpAddr, u8)> {
+    let path = req.url.path();
+    if path.len() <= index {
+        return None;
+    }
+    parse_network(&path[index..].join("/"))
+}
+
+/// Returns the first value of the given query parameter.
+pub fn query_param(req: &Request, key: &str) -> Option<String> {
+    let url: &iron::url::Url = req.url.as_ref();
+    url.query_pairs()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Response format requested by the client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Text,
+    /// Neither format is accepted, the route picks its default.
+    Default,
+}
+
+/// Picks the format of the accepted media type with the highest
+/// quality.
+pub fn negotiate(req: &Request) -> Format {
+    let mut mimes = match req.headers.get::<Accept>() {
+        Some(Accept(mimes)) => mimes.clone(),
+        None => return Format::Default,
+    };
+    mimes.sort_by_key(|mime| Reverse(mime.quality));
+    mimes
+        .into_iter()
+        .find_map(|mime| match mime.item {
+            Mime(TopLevel::Application, SubLevel::Json, _)
+            | Mime(TopLevel::Application, SubLevel::Star, _)
+            | Mime(TopLevel::Star, _, _) => Some(Format::Json),
+            Mime(TopLevel::Text, SubLevel::Plain, _)
+            | Mime(TopLevel::Text, SubLevel::Star, _) => {
+                Some(Format::Text)
+            }
+            _ => None,
+        })
+        .unwrap_or(Format::Default)
+}
+
 pub fn method_not_allowed(
     allowed_methods: Vec<Method>,
 ) -> IronResult<Response> {
@@ -93,6 +262,52 @@ macro_rules! req_db_op {
     };
 }
 
+/// Runs the block with the database connection of the request, given
+/// as `&dyn Any` like the connections used by `db_op`.
+#[macro_export]
+macro_rules! req_conn_op {
+    ($req:expr, $conn:ident $op: block) => {
+        match $req.db_type() {
+            DBType::MYSQL => {
+                let conn: DieselPooledConnection<
+                    diesel::mysql::MysqlConnection,
+                > = $req.db_conn().map_err(|err| {
+                    IronError::new(
+                        err,
+                        status::InternalServerError,
+                    )
+                })?;
+                let $conn: &dyn std::any::Any = &conn;
+                $op
+            }
+            DBType::POSTGRES => {
+                let conn: DieselPooledConnection<
+                    diesel::pg::PgConnection,
+                > = $req.db_conn().map_err(|err| {
+                    IronError::new(
+                        err,
+                        status::InternalServerError,
+                    )
+                })?;
+                let $conn: &dyn std::any::Any = &conn;
+                $op
+            }
+            DBType::SQLITE => {
+                let conn: DieselPooledConnection<
+                    diesel::sqlite::SqliteConnection,
+                > = $req.db_conn().map_err(|err| {
+                    IronError::new(
+                        err,
+                        status::InternalServerError,
+                    )
+                })?;
+                let $conn: &dyn std::any::Any = &conn;
+                $op
+            }
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! db_op {
     ($db_conn:expr, $db_type:expr, $conn:ident $op: block) => {
@@ -127,3 +342,172 @@ macro_rules! db_op {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(value: &str) -> IpAddr {
+        value.parse().expect("Invalid address")
+    }
+
+    #[test]
+    fn parse_network_defaults_to_host() {
+        assert_eq!(
+            parse_network("10.0.0.1"),
+            Some((addr("10.0.0.1"), 32))
+        );
+        assert_eq!(
+            parse_network("2001:db8::1"),
+            Some((addr("2001:db8::1"), 128))
+        );
+        assert_eq!(
+            parse_network(" 10.0.0.0/8 "),
+            Some((addr("10.0.0.0"), 8))
+        );
+    }
+
+    #[test]
+    fn parse_network_checks_prefix_bounds() {
+        assert_eq!(
+            parse_network("0.0.0.0/0"),
+            Some((addr("0.0.0.0"), 0))
+        );
+        assert_eq!(
+            parse_network("10.0.0.1/32"),
+            Some((addr("10.0.0.1"), 32))
+        );
+        assert_eq!(parse_network("10.0.0.1/33"), None);
+        assert_eq!(
+            parse_network("2001:db8::/128"),
+            Some((addr("2001:db8::"), 128))
+        );
+        assert_eq!(parse_network("2001:db8::/129"), None);
+        assert_eq!(parse_network("10.0.0.0/-1"), None);
+        assert_eq!(parse_network("10.0.0.0/"), None);
+        assert_eq!(parse_network("10.0.0/8"), None);
+    }
+
+    #[test]
+    fn convert_to_raw_clears_host_bits() {
+        assert_eq!(
+            convert_to_raw(addr("10.1.2.3"), 8),
+            (vec![10, 0, 0, 0], IpType::Ipv4)
+        );
+        assert_eq!(
+            convert_to_raw(addr("10.1.255.3"), 20),
+            (vec![10, 1, 240, 0], IpType::Ipv4)
+        );
+        assert_eq!(
+            convert_to_raw(addr("10.1.2.3"), 0),
+            (vec![0, 0, 0, 0], IpType::Ipv4)
+        );
+        assert_eq!(
+            convert_to_raw(addr("10.1.2.3"), 32),
+            (vec![10, 1, 2, 3], IpType::Ipv4)
+        );
+        let (raw_ip, ip_type) =
+            convert_to_raw(addr("2001:db8::ff"), 120);
+        assert_eq!(ip_type, IpType::Ipv6);
+        assert_eq!(raw_ip.len(), 16);
+        assert_eq!(raw_ip[15], 0);
+        let (raw_ip, _) =
+            convert_to_raw(addr("2001:db8::ff"), 128);
+        assert_eq!(raw_ip[15], 0xff);
+    }
+
+    #[test]
+    fn convert_network_round_trips() {
+        let (raw_ip, ip_type) =
+            convert_to_raw(addr("10.1.2.3"), 8);
+        assert_eq!(
+            convert_network(&raw_ip, ip_type, 8),
+            Some(String::from("10.0.0.0/8"))
+        );
+        assert_eq!(
+            convert_network(&raw_ip, ip_type, 32),
+            Some(String::from("10.0.0.0"))
+        );
+        assert_eq!(convert_network(&raw_ip, ip_type, 33), None);
+        assert_eq!(convert_network(&raw_ip, ip_type, -1), None);
+        assert_eq!(
+            convert_network(&raw_ip, IpType::Ipv6, 8),
+            None
+        );
+    }
+
+    #[test]
+    fn containing_networks_cover_every_prefix() {
+        let networks = containing_networks(addr("10.1.2.3"));
+        assert!(networks.contains(&vec![0, 0, 0, 0]));
+        assert!(networks.contains(&vec![10, 0, 0, 0]));
+        assert!(networks.contains(&vec![10, 1, 2, 0]));
+        assert!(networks.contains(&vec![10, 1, 2, 3]));
+        assert!(!networks.contains(&vec![10, 1, 2, 4]));
+        // Prefixes only yield a new network where a bit is set, so
+        // 10.1.2.3 with six set bits has seven distinct networks
+        assert_eq!(networks.len(), 7);
+        let networks = containing_networks(addr("2001:db8::1"));
+        assert!(networks
+            .iter()
+            .all(|network| network.len() == 16));
+        assert!(networks.contains(&vec![0; 16]));
+    }
+
+    #[test]
+    fn networks_overlap_compares_shorter_prefix() {
+        let (a, a_type) = convert_to_raw(addr("10.0.0.0"), 8);
+        let (b, b_type) = convert_to_raw(addr("10.1.2.3"), 32);
+        let (c, c_type) = convert_to_raw(addr("11.0.0.0"), 8);
+        assert!(networks_overlap(
+            (&a, a_type, 8),
+            (&b, b_type, 32)
+        ));
+        assert!(networks_overlap(
+            (&b, b_type, 32),
+            (&a, a_type, 8)
+        ));
+        assert!(!networks_overlap(
+            (&a, a_type, 8),
+            (&c, c_type, 8)
+        ));
+        assert!(networks_overlap(
+            (&a, a_type, 0),
+            (&c, c_type, 8)
+        ));
+        assert!(networks_overlap(
+            (&b, b_type, 32),
+            (&b, b_type, 32)
+        ));
+        let (d, d_type) = convert_to_raw(addr("10.1.2.4"), 32);
+        assert!(!networks_overlap(
+            (&b, b_type, 32),
+            (&d, d_type, 32)
+        ));
+        assert!(networks_overlap(
+            (&b, b_type, 29),
+            (&d, d_type, 29)
+        ));
+        assert!(!networks_overlap(
+            (&b, b_type, 30),
+            (&d, d_type, 30)
+        ));
+    }
+
+    #[test]
+    fn networks_overlap_separates_ip_types() {
+        let (a, a_type) = convert_to_raw(addr("0.0.0.0"), 0);
+        let (b, b_type) = convert_to_raw(addr("::"), 0);
+        assert!(!networks_overlap(
+            (&a, a_type, 0),
+            (&b, b_type, 0)
+        ));
+        let (c, c_type) =
+            convert_to_raw(addr("2001:db8::1"), 128);
+        let (d, d_type) = convert_to_raw(addr("2001:db8::"), 32);
+        assert!(networks_overlap(
+            (&c, c_type, 128),
+            (&d, d_type, 32)
+        ));
+    }
+}
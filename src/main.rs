@@ -1,35 +1,81 @@
 #[allow(unused_imports)]
 #[macro_use]
 extern crate diesel;
+#[macro_use]
+extern crate diesel_migrations;
 
 mod args;
+mod config;
 mod execute;
+mod commands {
+    pub mod admin;
+    pub mod migrate;
+    pub mod token;
+    pub mod update;
+}
 pub mod helper;
 mod backend {
     pub mod abuseipdb;
+    pub mod provider;
+    pub mod reserved;
+    pub mod schedule;
+    pub mod spamhaus;
+    pub mod store;
+    pub mod url_list;
 }
 mod routes {
+    pub mod admin;
+    pub mod allowlist;
     pub mod api;
+    pub mod changes;
+    pub mod check;
+    pub mod history;
+    pub mod report;
     pub mod stats;
 }
 mod middleware {
+    pub mod auth;
     pub mod diesel;
     pub mod logger;
+    pub mod schedule;
 }
 mod types {
+    pub mod allowlist;
+    pub mod api_token;
     pub mod backend_type;
     pub mod blacklist;
+    pub mod change;
+    pub mod check;
+    pub mod history;
     pub mod ip_type;
+    pub mod rate_limit;
+    pub mod report;
+    pub mod source;
     pub mod stats;
 }
+mod migrations;
 mod schema;
 
-use crate::{args::get_arguments, execute::execute};
+use crate::{
+    args::{get_arguments, Command},
+    execute::execute,
+};
 
 fn main() {
     let args = get_arguments();
-    if let Some(log_level) = args.log_level {
-        simple_logger::init_with_level(log_level).unwrap();
+    match &args.command {
+        Command::Serve => execute(&args),
+        Command::Migrate { dry_run } => {
+            commands::migrate::execute(&args, *dry_run)
+        }
+        Command::Update { providers } => {
+            commands::update::execute(&args, providers)
+        }
+        Command::Admin(command) => {
+            commands::admin::execute(&args, command)
+        }
+        Command::Token(command) => {
+            commands::token::execute(&args, command)
+        }
     }
-    execute(&args);
 }
@@ -0,0 +1,120 @@
+use crate::{
+    middleware::diesel::{
+        DBType, DieselPooledConnection, DieselReqExt,
+        DieselTypeExt,
+    },
+    req_db_op,
+    schema::api_token::dsl::*,
+    types::api_token::{hash_token, ApiToken, TokenScope},
+};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use iron::{
+    headers::{Authorization, Bearer},
+    method::Method,
+    status, typemap, BeforeMiddleware, IronError, IronResult,
+    Request,
+};
+use std::{error::Error, fmt};
+
+#[derive(Debug)]
+pub struct AuthError(&'static str);
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for AuthError {}
+
+/// Name of the token used to authenticate the request. Missing if the
+/// request was served anonymously.
+pub struct TokenName;
+
+impl typemap::Key for TokenName {
+    type Value = String;
+}
+
+/// Iron middleware checking `Authorization: Bearer` tokens against the
+/// scope required by the requested route.
+pub struct Auth {
+    /// Whether read requests are allowed without a token.
+    pub anonymous_read: bool,
+}
+
+impl Auth {
+    pub fn new(anonymous_read: bool) -> Self {
+        Auth { anonymous_read }
+    }
+}
+
+/// The scope required to access the requested route. Health checks are
+/// always public. Lookups using `POST /api/check` only read data.
+fn required_scope(req: &Request) -> Option<TokenScope> {
+    let path = req.url.path();
+    let route = (
+        path.first().cloned().unwrap_or(""),
+        path.get(1).cloned().unwrap_or(""),
+    );
+    match (&req.method, route) {
+        (_, ("api", "health"))
+        | (_, ("api", "system_health")) => None,
+        (_, ("api", "admin")) => Some(TokenScope::Admin),
+        (Method::Get, _) | (Method::Head, _) => {
+            Some(TokenScope::Read)
+        }
+        (Method::Post, ("api", "check")) => {
+            Some(TokenScope::Read)
+        }
+        _ => Some(TokenScope::Write),
+    }
+}
+
+impl BeforeMiddleware for Auth {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        let required = match required_scope(req) {
+            Some(required) => required,
+            None => return Ok(()),
+        };
+        let token = req
+            .headers
+            .get::<Authorization<Bearer>>()
+            .map(|auth| auth.0.token.clone());
+        let token = match token {
+            Some(token) => token,
+            None if self.anonymous_read
+                && required == TokenScope::Read =>
+            {
+                return Ok(())
+            }
+            None => {
+                return Err(IronError::new(
+                    AuthError("Missing token"),
+                    (status::Unauthorized, "Missing token"),
+                ))
+            }
+        };
+
+        let token_query =
+            api_token.filter(token_hash.eq(hash_token(&token)));
+        let mut values: Vec<ApiToken> =
+            req_db_op!(req, token_query, load);
+        let entry = match values.pop() {
+            Some(entry) => entry,
+            None => {
+                return Err(IronError::new(
+                    AuthError("Invalid token"),
+                    (status::Unauthorized, "Invalid token"),
+                ))
+            }
+        };
+        if !entry.scope.allows(required) {
+            return Err(IronError::new(
+                AuthError("Insufficient scope"),
+                (status::Forbidden, "Insufficient scope"),
+            ));
+        }
+        req.extensions.insert::<TokenName>(entry.name);
+        Ok(())
+    }
+}
@@ -2,7 +2,7 @@
 
 use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
 use iron::{prelude::*, typemap, BeforeMiddleware};
-use std::{error::Error, fmt};
+use std::{error::Error, fmt, time::Duration};
 
 #[derive(Debug, Clone, Copy)]
 pub enum DBType {
@@ -36,6 +36,15 @@ impl typemap::Key for DBType {
     type Value = DBType;
 }
 
+/// Settings of the connection pool. Connections idle for longer than
+/// `idle_timeout` are closed, `None` keeps them open.
+#[derive(Debug, Clone, Copy)]
+pub struct PoolConfig {
+    pub max_size: u32,
+    pub connection_timeout: Duration,
+    pub idle_timeout: Option<Duration>,
+}
+
 /// The type of the pool stored in `DieselMiddleware`.
 pub type DieselPool<T> = Pool<ConnectionManager<T>>;
 
@@ -67,12 +76,15 @@ impl<T: diesel::Connection> DieselMiddleware<T> {
     pub fn new(
         connection_str: &str,
         db_type: DBType,
+        pool_config: PoolConfig,
     ) -> Result<DieselMiddleware<T>, Box<dyn Error>> {
         let manager = ConnectionManager::<T>::new(connection_str);
-        Ok(Self::new_with_pool(
-            Pool::builder().build(manager)?,
-            db_type,
-        ))
+        let pool = Pool::builder()
+            .max_size(pool_config.max_size)
+            .connection_timeout(pool_config.connection_timeout)
+            .idle_timeout(pool_config.idle_timeout)
+            .build(manager)?;
+        Ok(Self::new_with_pool(pool, db_type))
     }
     /// Creates a instance of the middleware with the ability to provide a preconfigured pool.
     pub fn new_with_pool(
@@ -0,0 +1,20 @@
+use crate::backend::schedule::SchedulerState;
+use iron::{typemap, BeforeMiddleware, IronResult, Request};
+
+/// Shares the planned provider updates and the update runs with the
+/// request handlers.
+pub struct ScheduleMiddleware {
+    pub state: SchedulerState,
+}
+
+impl typemap::Key for ScheduleMiddleware {
+    type Value = SchedulerState;
+}
+
+impl BeforeMiddleware for ScheduleMiddleware {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        req.extensions
+            .insert::<ScheduleMiddleware>(self.state.clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{
+    db_op,
+    middleware::diesel::{DBType, DieselPooledConnection},
+};
+use diesel::{dsl::sql, sql_types::Bool, RunQueryDsl};
+use diesel_migrations::{
+    run_migrations, Migration, MigrationConnection,
+    RunMigrationsError,
+};
+use std::{any::Any, io::Write};
+
+macro_rules! embed_migrations_as {
+    ($name:ident, $path:expr) => {
+        #[allow(dead_code, bare_trait_objects)]
+        mod $name {
+            #[derive(EmbedMigrations)]
+            #[embed_migrations_options(migrations_path = $path)]
+            struct _Dummy;
+
+            pub fn all() -> &'static [&'static dyn Migration] {
+                ALL_MIGRATIONS
+            }
+        }
+    };
+}
+
+embed_migrations_as!(mysql, "migrations.mysql");
+embed_migrations_as!(postgres, "migrations.postgres");
+embed_migrations_as!(sqlite, "migrations.sqlite");
+
+/// The migrations compiled into the binary for the given database.
+fn embedded(
+    db_type: DBType,
+) -> &'static [&'static dyn Migration] {
+    match db_type {
+        DBType::MYSQL => mysql::all(),
+        DBType::POSTGRES => postgres::all(),
+        DBType::SQLITE => sqlite::all(),
+    }
+}
+
+/// Query checking whether the table of the applied migrations exists.
+fn migrations_table_query(db_type: DBType) -> &'static str {
+    match db_type {
+        DBType::MYSQL => "EXISTS (SELECT 1 FROM information_schema.tables WHERE table_schema = DATABASE() AND table_name = '__diesel_schema_migrations')",
+        DBType::POSTGRES => "EXISTS (SELECT 1 FROM information_schema.tables WHERE table_schema = current_schema() AND table_name = '__diesel_schema_migrations')",
+        DBType::SQLITE => "EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '__diesel_schema_migrations')",
+    }
+}
+
+/// Returns the versions of all embedded migrations which were not yet
+/// applied to the database, oldest first. The database is not
+/// modified.
+pub fn pending(
+    db_conn: &dyn Any,
+    db_type: DBType,
+) -> Result<Vec<String>, RunMigrationsError> {
+    let query = migrations_table_query(db_type);
+    let applied = db_op!(db_conn, db_type, conn {
+        if diesel::select(sql::<Bool>(query)).get_result::<bool>(conn)? {
+            conn.previously_run_migration_versions()?
+        } else {
+            Default::default()
+        }
+    });
+    let mut versions: Vec<String> = embedded(db_type)
+        .iter()
+        .map(|migration| migration.version().to_string())
+        .filter(|version| !applied.contains(version))
+        .collect();
+    versions.sort();
+    Ok(versions)
+}
+
+/// Applies all pending migrations. Every migration runs in its own
+/// transaction and is reported to `out`.
+pub fn run(
+    db_conn: &dyn Any,
+    db_type: DBType,
+    out: &mut dyn Write,
+) -> Result<(), RunMigrationsError> {
+    let migrations = embedded(db_type);
+    db_op!(db_conn, db_type, conn {
+        run_migrations(&**conn, migrations.iter().copied(), out)
+    })
+}
@@ -0,0 +1,112 @@
+use crate::{
+    helper::{
+        method_not_allowed, negotiate, query_param, Format,
+    },
+    middleware::schedule::ScheduleMiddleware,
+};
+use chrono::Utc;
+use iron::{
+    error::IronError, headers::ContentType, method::Method,
+    status, IronResult, Request, Response,
+};
+use log::debug;
+
+pub fn index(req: &mut Request) -> IronResult<Response> {
+    #[allow(clippy::map_clone)]
+    match req.url.path().get(2).map(|&t| t).unwrap_or("") {
+        "update" => update(req),
+        _ => Ok(Response::with(status::NotFound)),
+    }
+}
+
+fn update(req: &mut Request) -> IronResult<Response> {
+    match &req.method {
+        Method::Get => update_get(req),
+        Method::Post => update_post(req),
+        _ => method_not_allowed(vec![Method::Get, Method::Post]),
+    }
+}
+
+fn update_get(req: &mut Request) -> IronResult<Response> {
+    match negotiate(req) {
+        Format::Json => update_get_json(req),
+        Format::Text => update_get_text(req),
+        Format::Default => update_get_default(req),
+    }
+}
+
+fn update_get_default(req: &mut Request) -> IronResult<Response> {
+    update_get_json(req)
+}
+
+fn update_get_json(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving update_get_json request");
+    let state = match req.extensions.get::<ScheduleMiddleware>() {
+        Some(state) => state.clone(),
+        None => {
+            return Ok(Response::with(status::ServiceUnavailable))
+        }
+    };
+    let update_status =
+        state.update_runs.status(state.next_runs.due(Utc::now()));
+    let json =
+        serde_json::to_string(&update_status).map_err(|err| {
+            IronError::new(err, status::InternalServerError)
+        })?;
+    let mut response = Response::with((status::Ok, json));
+    response.headers.set(ContentType::json());
+    Ok(response)
+}
+
+fn update_get_text(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving update_get_text request");
+    let state = match req.extensions.get::<ScheduleMiddleware>() {
+        Some(state) => state.clone(),
+        None => {
+            return Ok(Response::with(status::ServiceUnavailable))
+        }
+    };
+    let update_status =
+        state.update_runs.status(state.next_runs.due(Utc::now()));
+    let mut response =
+        Response::with((status::Ok, update_status.to_plain()));
+    response.headers.set(ContentType::plaintext());
+    Ok(response)
+}
+
+/// Queues an immediate update of the providers given by the
+/// `provider` parameter, every provider if it is missing. The update
+/// runs on the scheduler thread, the request does not wait for it.
+fn update_post(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving update_post request");
+    let names: Vec<String> = query_param(req, "provider")
+        .map(|value| {
+            value
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    let state = match req.extensions.get::<ScheduleMiddleware>() {
+        Some(state) => state.clone(),
+        None => {
+            return Ok(Response::with(status::ServiceUnavailable))
+        }
+    };
+    let queued = match state.next_runs.queue(&names, Utc::now()) {
+        Ok(queued) => queued,
+        Err(name) => {
+            return Ok(Response::with((
+                status::BadRequest,
+                format!("Unknown provider {}", name),
+            )))
+        }
+    };
+    let json = serde_json::to_string(&queued).map_err(|err| {
+        IronError::new(err, status::InternalServerError)
+    })?;
+    let mut response = Response::with((status::Accepted, json));
+    response.headers.set(ContentType::json());
+    Ok(response)
+}
@@ -0,0 +1,156 @@
+use crate::{
+    backend::store,
+    helper::{
+        convert_to_raw, method_not_allowed, negotiate,
+        parse_network, path_network, Format,
+    },
+    middleware::diesel::{
+        DBType, DieselPooledConnection, DieselReqExt,
+        DieselTypeExt,
+    },
+    req_conn_op, req_db_op,
+    schema::allowlist::dsl::*,
+    types::allowlist::{Allowlist, NewAllowlist},
+};
+use diesel::RunQueryDsl;
+use iron::{
+    error::IronError, headers::ContentType, method::Method,
+    status, IronResult, Request, Response,
+};
+use log::debug;
+use std::io::Read;
+
+pub fn index(req: &mut Request) -> IronResult<Response> {
+    let has_network = req
+        .url
+        .path()
+        .get(2)
+        .map(|segment| !segment.is_empty())
+        .unwrap_or(false);
+    if has_network {
+        match &req.method {
+            Method::Delete => allowlist_delete(req),
+            _ => method_not_allowed(vec![Method::Delete]),
+        }
+    } else {
+        match &req.method {
+            Method::Get => allowlist_get(req),
+            Method::Post => allowlist_post(req),
+            _ => method_not_allowed(vec![
+                Method::Get,
+                Method::Post,
+            ]),
+        }
+    }
+}
+
+fn allowlist_get(req: &mut Request) -> IronResult<Response> {
+    match negotiate(req) {
+        Format::Json => allowlist_get_json(req),
+        Format::Text => allowlist_get_text(req),
+        Format::Default => allowlist_get_default(req),
+    }
+}
+
+fn allowlist_get_default(
+    req: &mut Request,
+) -> IronResult<Response> {
+    allowlist_get_json(req)
+}
+
+fn allowlist_get_json(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving allowlist_get_json request");
+    let values: Vec<Allowlist> = req_db_op!(req, allowlist, load);
+    let json = serde_json::to_string(&values).map_err(|err| {
+        IronError::new(err, status::InternalServerError)
+    })?;
+    let mut response = Response::with((status::Ok, json));
+    response.headers.set(ContentType::json());
+    Ok(response)
+}
+
+fn allowlist_get_text(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving allowlist_get_text request");
+    let values: Vec<Allowlist> = req_db_op!(req, allowlist, load);
+    let plain = values
+        .iter()
+        .filter_map(Allowlist::to_plain)
+        .collect::<Vec<String>>()
+        .join("\n");
+    let mut response = Response::with((status::Ok, plain));
+    response.headers.set(ContentType::plaintext());
+    Ok(response)
+}
+
+fn allowlist_post(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving allowlist_post request");
+    let new_entry: NewAllowlist = serde_json::from_reader(
+        req.body.by_ref(),
+    )
+    .map_err(|err| IronError::new(err, status::BadRequest))?;
+    let (address, length) = match parse_network(&new_entry.ip) {
+        Some(network) => network,
+        None => {
+            return Ok(Response::with((
+                status::BadRequest,
+                "Invalid ip or network",
+            )))
+        }
+    };
+    let entry = Allowlist::new(
+        address,
+        length,
+        new_entry.comment,
+        new_entry.created_by,
+    );
+
+    let db_type = req.db_type();
+    req_conn_op!(req, conn {
+        store::store_allowlist(
+            conn,
+            db_type,
+            std::slice::from_ref(&entry),
+        )
+    })
+    .map_err(|err| {
+        IronError::new(err, status::InternalServerError)
+    })?;
+
+    let json = serde_json::to_string(&entry).map_err(|err| {
+        IronError::new(err, status::InternalServerError)
+    })?;
+    let mut response = Response::with((status::Created, json));
+    response.headers.set(ContentType::json());
+    Ok(response)
+}
+
+fn allowlist_delete(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving allowlist_delete request");
+    let (address, length) = match path_network(req, 2) {
+        Some(network) => network,
+        None => {
+            return Ok(Response::with((
+                status::BadRequest,
+                "Invalid ip or network",
+            )))
+        }
+    };
+    let (raw_ip, raw_type) = convert_to_raw(address, length);
+    let db_type = req.db_type();
+    let deleted = req_conn_op!(req, conn {
+        store::remove_allowlist(
+            conn,
+            db_type,
+            &raw_ip,
+            raw_type,
+            i16::from(length),
+        )
+    })
+    .map_err(|err| {
+        IronError::new(err, status::InternalServerError)
+    })?;
+    if deleted == 0 {
+        return Ok(Response::with(status::NotFound));
+    }
+    Ok(Response::with(status::NoContent))
+}
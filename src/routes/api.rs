@@ -1,67 +1,81 @@
 use crate::{
-    helper::method_not_allowed,
+    backend::store,
+    helper::{
+        convert_to_raw, method_not_allowed, negotiate,
+        parse_network, path_network, Format,
+    },
     middleware::diesel::{
         DBType, DieselPooledConnection, DieselReqExt,
         DieselTypeExt,
     },
-    req_db_op, schema,
-    types::blacklist::Blacklist,
+    req_conn_op, req_db_op,
+    routes::{admin, allowlist, changes, check, history, report},
+    schema::{self, blacklist::dsl::expires},
+    types::{
+        allowlist::{is_allowlisted, Allowlist},
+        backend_type::BackendType,
+        blacklist::{Blacklist, NewBlacklist},
+        check::CheckEntry,
+    },
+};
+use chrono::Utc;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, QueryDsl,
+    RunQueryDsl,
 };
-use diesel::RunQueryDsl;
 use iron::{
-    error::IronError,
-    headers::{Accept, ContentType},
-    method::Method,
-    mime::{Mime, SubLevel, TopLevel},
+    error::IronError, headers::ContentType, method::Method,
     status, IronResult, Request, Response,
 };
 use log::debug;
+use std::io::Read;
 
 pub fn index(req: &mut Request) -> IronResult<Response> {
     #[allow(clippy::map_clone)]
     match req.url.path().get(1).map(|&t| t).unwrap_or("") {
+        "admin" => admin::index(req),
+        "allowlist" => allowlist::index(req),
         "blacklist" => blacklist(req),
+        "changes" => changes::index(req),
+        "check" => check::index(req),
         "health" => health(req),
+        "history" => history::index(req),
+        "report" => report::index(req),
         "system_health" => system_health(req),
         _ => Ok(Response::with(status::NotFound)),
     }
 }
 
 fn blacklist(req: &mut Request) -> IronResult<Response> {
-    match &req.method {
-        Method::Get => blacklist_get(req),
-        _ => method_not_allowed(vec![Method::Get]),
+    let has_network = req
+        .url
+        .path()
+        .get(2)
+        .map(|segment| !segment.is_empty())
+        .unwrap_or(false);
+    if has_network {
+        match &req.method {
+            Method::Delete => blacklist_delete(req),
+            _ => method_not_allowed(vec![Method::Delete]),
+        }
+    } else {
+        match &req.method {
+            Method::Get => blacklist_get(req),
+            Method::Post => blacklist_post(req),
+            _ => method_not_allowed(vec![
+                Method::Get,
+                Method::Post,
+            ]),
+        }
     }
 }
 
 fn blacklist_get(req: &mut Request) -> IronResult<Response> {
-    if let Some(Accept(mimes)) = req.headers.get() {
-        let mut mimes = mimes.clone();
-        mimes.sort_by(|a, b| b.quality.cmp(&a.quality));
-        for mime in mimes {
-            match mime.item {
-                Mime(
-                    TopLevel::Application,
-                    SubLevel::Json,
-                    _,
-                )
-                | Mime(
-                    TopLevel::Application,
-                    SubLevel::Star,
-                    _,
-                )
-                | Mime(TopLevel::Star, _, _) => {
-                    return blacklist_get_json(req)
-                }
-                Mime(TopLevel::Text, SubLevel::Plain, _)
-                | Mime(TopLevel::Text, SubLevel::Star, _) => {
-                    return blacklist_get_text(req)
-                }
-                _ => {}
-            }
-        }
+    match negotiate(req) {
+        Format::Json => blacklist_get_json(req),
+        Format::Text => blacklist_get_text(req),
+        Format::Default => blacklist_get_default(req),
     }
-    blacklist_get_default(req)
 }
 
 fn blacklist_get_default(
@@ -72,9 +86,7 @@ fn blacklist_get_default(
 
 fn blacklist_get_json(req: &mut Request) -> IronResult<Response> {
     debug!("Serving blacklist_get_json request");
-    let blacklist_query = schema::blacklist::dsl::blacklist;
-    let values: Vec<Blacklist> =
-        req_db_op!(req, blacklist_query, load);
+    let values = load_blacklist(req)?;
     let json = serde_json::to_string(&values).map_err(|err| {
         IronError::new(err, status::InternalServerError)
     })?;
@@ -85,9 +97,7 @@ fn blacklist_get_json(req: &mut Request) -> IronResult<Response> {
 
 fn blacklist_get_text(req: &mut Request) -> IronResult<Response> {
     debug!("Serving blacklist_get_text request");
-    let blacklist_query = schema::blacklist::dsl::blacklist;
-    let values: Vec<Blacklist> =
-        req_db_op!(req, blacklist_query, load);
+    let values = load_blacklist(req)?;
     let plain = values
         .iter()
         .filter_map(Blacklist::to_plain)
@@ -98,6 +108,118 @@ fn blacklist_get_text(req: &mut Request) -> IronResult<Response> {
     Ok(response)
 }
 
+fn blacklist_post(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving blacklist_post request");
+    let new_entry: NewBlacklist = serde_json::from_reader(
+        req.body.by_ref(),
+    )
+    .map_err(|err| IronError::new(err, status::BadRequest))?;
+    let (address, length) = match parse_network(&new_entry.ip) {
+        Some(network) => network,
+        None => {
+            return Ok(Response::with((
+                status::BadRequest,
+                "Invalid ip or network",
+            )))
+        }
+    };
+    let mut entry = match Blacklist::new_network(
+        address,
+        length,
+        BackendType::Manual,
+        new_entry.reason,
+    ) {
+        Some(entry) => entry,
+        None => {
+            return Ok(Response::with((
+                status::BadRequest,
+                "Invalid ip or network",
+            )))
+        }
+    };
+    entry.expires = new_entry.ttl.map(|ttl| {
+        entry.last_update
+            + chrono::Duration::seconds(i64::from(ttl))
+    });
+
+    store_manual(req, &entry)?;
+
+    let json = serde_json::to_string(&CheckEntry::from(&entry))
+        .map_err(|err| {
+        IronError::new(err, status::InternalServerError)
+    })?;
+    let mut response = Response::with((status::Created, json));
+    response.headers.set(ContentType::json());
+    Ok(response)
+}
+
+/// Stores a manual entry, replacing an existing entry of the same
+/// network.
+pub fn store_manual(
+    req: &mut Request,
+    entry: &Blacklist,
+) -> IronResult<()> {
+    let db_type = req.db_type();
+    req_conn_op!(req, conn {
+        store::store_manual(conn, db_type, entry)
+    })
+    .map_err(|err| {
+        IronError::new(err, status::InternalServerError)
+    })
+}
+
+/// Removes every entry of the given network, regardless of the
+/// provider. Provider entries may be added again on the next update.
+fn blacklist_delete(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving blacklist_delete request");
+    let (address, length) = match path_network(req, 2) {
+        Some(network) => network,
+        None => {
+            return Ok(Response::with((
+                status::BadRequest,
+                "Invalid ip or network",
+            )))
+        }
+    };
+    let (raw_ip, raw_type) = convert_to_raw(address, length);
+    let db_type = req.db_type();
+    let deleted = req_conn_op!(req, conn {
+        store::remove_network(
+            conn,
+            db_type,
+            &raw_ip,
+            raw_type,
+            i16::from(length),
+        )
+    })
+    .map_err(|err| {
+        IronError::new(err, status::InternalServerError)
+    })?;
+    if deleted == 0 {
+        return Ok(Response::with(status::NotFound));
+    }
+    Ok(Response::with(status::NoContent))
+}
+
+/// Loads every entry which is neither expired nor covered by the
+/// allowlist.
+pub fn load_blacklist(
+    req: &mut Request,
+) -> IronResult<Vec<Blacklist>> {
+    let allowlist_query = schema::allowlist::dsl::allowlist;
+    let allowed: Vec<Allowlist> =
+        req_db_op!(req, allowlist_query, load);
+    let now = Utc::now().naive_utc();
+    let blacklist_query = schema::blacklist::dsl::blacklist
+        .filter(expires.is_null().or(expires.gt(now)));
+    let values: Vec<Blacklist> =
+        req_db_op!(req, blacklist_query, load);
+    Ok(values
+        .into_iter()
+        .filter(|value| !is_allowlisted(&allowed, value))
+        .collect())
+}
+
 fn health(req: &mut Request) -> IronResult<Response> {
     match &req.method {
         Method::Get => health_get(req),
@@ -0,0 +1,159 @@
+use crate::{
+    helper::{
+        method_not_allowed, negotiate, query_param, Format,
+    },
+    middleware::diesel::{
+        DBType, DieselPooledConnection, DieselReqExt,
+        DieselTypeExt,
+    },
+    req_db_op,
+    routes::api::load_blacklist,
+    schema::{allowlist, blacklist_change::dsl::*},
+    types::{
+        allowlist::{is_network_allowlisted, Allowlist},
+        blacklist::Blacklist,
+        change::{Change, ChangeType, Changes},
+    },
+};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use iron::{
+    error::IronError, headers::ContentType, method::Method,
+    status, IronResult, Request, Response,
+};
+use log::debug;
+
+pub fn index(req: &mut Request) -> IronResult<Response> {
+    match &req.method {
+        Method::Get => changes_get(req),
+        _ => method_not_allowed(vec![Method::Get]),
+    }
+}
+
+fn changes_get(req: &mut Request) -> IronResult<Response> {
+    match negotiate(req) {
+        Format::Json => changes_get_json(req),
+        Format::Text => changes_get_text(req),
+        Format::Default => changes_get_default(req),
+    }
+}
+
+fn changes_get_default(
+    req: &mut Request,
+) -> IronResult<Response> {
+    changes_get_json(req)
+}
+
+fn changes_get_json(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving changes_get_json request");
+    let changes = match load_changes(req)? {
+        Ok(changes) => changes,
+        Err(response) => return Ok(response),
+    };
+    let json =
+        serde_json::to_string(&changes).map_err(|err| {
+            IronError::new(err, status::InternalServerError)
+        })?;
+    let mut response = Response::with((status::Ok, json));
+    response.headers.set(ContentType::json());
+    Ok(response)
+}
+
+fn changes_get_text(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving changes_get_text request");
+    let changes = match load_changes(req)? {
+        Ok(changes) => changes,
+        Err(response) => return Ok(response),
+    };
+    let mut response =
+        Response::with((status::Ok, changes.to_plain()));
+    response.headers.set(ContentType::plaintext());
+    response.headers.set_raw(
+        "X-Cursor",
+        vec![changes.cursor.to_string().into_bytes()],
+    );
+    Ok(response)
+}
+
+/// Loads the changes since the cursor given by `since`. Without a
+/// cursor, the whole blacklist is returned as additions. Returns
+/// `410 Gone` if changes after the cursor were already pruned, in which
+/// case the client has to start over.
+fn load_changes(
+    req: &mut Request,
+) -> IronResult<Result<Changes, Response>> {
+    let since = match query_param(req, "since") {
+        Some(since) => match since.parse::<i64>() {
+            Ok(since) => Some(since),
+            Err(_) => {
+                return Ok(Err(Response::with((
+                    status::BadRequest,
+                    "Invalid cursor",
+                ))))
+            }
+        },
+        None => None,
+    };
+
+    let cursor_query =
+        blacklist_change.select(id).order_by(id.desc()).limit(1);
+    let mut cursor: Vec<i64> =
+        req_db_op!(req, cursor_query, load);
+    let cursor = cursor.pop().unwrap_or(0);
+
+    let since = match since {
+        Some(since) => since,
+        None => {
+            let values = load_blacklist(req)?;
+            return Ok(Ok(Changes {
+                cursor,
+                added: values
+                    .iter()
+                    .filter_map(Blacklist::to_plain)
+                    .collect(),
+                removed: Vec::new(),
+            }));
+        }
+    };
+
+    let oldest_query =
+        blacklist_change.select(id).order_by(id.asc()).limit(1);
+    let mut oldest: Vec<i64> =
+        req_db_op!(req, oldest_query, load);
+    if let Some(oldest) = oldest.pop() {
+        if since < oldest - 1 {
+            return Ok(Err(Response::with((
+                status::Gone,
+                "Cursor expired",
+            ))));
+        }
+    }
+
+    let changes_query = blacklist_change
+        .select((id, ip, ip_type, prefix, change_type))
+        .filter(id.gt(since))
+        .order_by(id.asc());
+    let values: Vec<Change> =
+        req_db_op!(req, changes_query, load);
+    let allowlist_query = allowlist::table;
+    let allowed: Vec<Allowlist> =
+        req_db_op!(req, allowlist_query, load);
+    let cursor = values
+        .last()
+        .map(|value| value.id)
+        .unwrap_or(since)
+        .max(cursor);
+    // Allowlisted networks are not served, so their additions are not
+    // either. Removals are kept as the allowlist may have been added
+    // after a client synced.
+    let values: Vec<Change> = values
+        .into_iter()
+        .filter(|value| {
+            value.change_type != ChangeType::Added
+                || !is_network_allowlisted(
+                    &allowed,
+                    (&value.ip, value.ip_type, value.prefix),
+                )
+        })
+        .collect();
+    Ok(Ok(Changes::new(cursor, &values)))
+}
@@ -0,0 +1,308 @@
+use crate::{
+    helper::{
+        containing_networks, convert_to_raw, max_prefix,
+        method_not_allowed, negotiate, networks_overlap, Format,
+    },
+    middleware::diesel::{
+        DBType, DieselPooledConnection, DieselReqExt,
+        DieselTypeExt,
+    },
+    req_db_op,
+    schema::{allowlist, blacklist::dsl::*, blacklist_source},
+    types::{
+        allowlist::{is_allowlisted, Allowlist},
+        blacklist::Blacklist,
+        check::{
+            BlacklistIndex, CheckEntry, CheckResult, SourceIndex,
+            MAX_CHECK_ADDRESSES, MAX_CHECK_BODY_SIZE,
+        },
+        source::Source,
+    },
+};
+use chrono::Utc;
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, QueryDsl,
+    RunQueryDsl,
+};
+use iron::{
+    error::IronError, headers::ContentType, method::Method,
+    status, IronResult, Request, Response,
+};
+use log::debug;
+use std::{collections::BTreeSet, io::Read, net::IpAddr};
+
+/// Number of networks looked up by a single query of a bulk check.
+const NETWORKS_PER_QUERY: usize = 500;
+
+pub fn index(req: &mut Request) -> IronResult<Response> {
+    let has_address = req
+        .url
+        .path()
+        .get(2)
+        .map(|segment| !segment.is_empty())
+        .unwrap_or(false);
+    if has_address {
+        match &req.method {
+            Method::Get => check_get(req),
+            _ => method_not_allowed(vec![Method::Get]),
+        }
+    } else {
+        match &req.method {
+            Method::Post => check_post(req),
+            _ => method_not_allowed(vec![Method::Post]),
+        }
+    }
+}
+
+fn check_get(req: &mut Request) -> IronResult<Response> {
+    match negotiate(req) {
+        Format::Json => check_get_json(req),
+        Format::Text => check_get_text(req),
+        Format::Default => check_get_default(req),
+    }
+}
+
+fn check_get_default(req: &mut Request) -> IronResult<Response> {
+    check_get_json(req)
+}
+
+fn check_get_json(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving check_get_json request");
+    let result = match check_address(req)? {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+    let json = serde_json::to_string(&result).map_err(|err| {
+        IronError::new(err, status::InternalServerError)
+    })?;
+    let mut response = Response::with((status::Ok, json));
+    response.headers.set(ContentType::json());
+    Ok(response)
+}
+
+fn check_get_text(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving check_get_text request");
+    let result = match check_address(req)? {
+        Ok(result) => result,
+        Err(response) => return Ok(response),
+    };
+    let mut response =
+        Response::with((status::Ok, result.to_plain()));
+    response.headers.set(ContentType::plaintext());
+    Ok(response)
+}
+
+fn check_post(req: &mut Request) -> IronResult<Response> {
+    match negotiate(req) {
+        Format::Json => check_post_json(req),
+        Format::Text => check_post_text(req),
+        Format::Default => check_post_default(req),
+    }
+}
+
+fn check_post_default(req: &mut Request) -> IronResult<Response> {
+    check_post_json(req)
+}
+
+fn check_post_json(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving check_post_json request");
+    let results = match check_addresses(req)? {
+        Ok(results) => results,
+        Err(response) => return Ok(response),
+    };
+    let json =
+        serde_json::to_string(&results).map_err(|err| {
+            IronError::new(err, status::InternalServerError)
+        })?;
+    let mut response = Response::with((status::Ok, json));
+    response.headers.set(ContentType::json());
+    Ok(response)
+}
+
+fn check_post_text(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving check_post_text request");
+    let results = match check_addresses(req)? {
+        Ok(results) => results,
+        Err(response) => return Ok(response),
+    };
+    let plain = results
+        .iter()
+        .map(CheckResult::to_plain)
+        .collect::<Vec<String>>()
+        .join("\n");
+    let mut response = Response::with((status::Ok, plain));
+    response.headers.set(ContentType::plaintext());
+    Ok(response)
+}
+
+/// Looks up every address given in the body. The body is either a json
+/// array of addresses or contains one address per line. Invalid
+/// addresses are ignored. Only listed addresses are returned.
+fn check_addresses(
+    req: &mut Request,
+) -> IronResult<Result<Vec<CheckResult>, Response>> {
+    let mut body = String::new();
+    req.body
+        .by_ref()
+        .take(MAX_CHECK_BODY_SIZE + 1)
+        .read_to_string(&mut body)
+        .map_err(|err| IronError::new(err, status::BadRequest))?;
+    if body.len() as u64 > MAX_CHECK_BODY_SIZE {
+        return Ok(Err(Response::with((
+            status::PayloadTooLarge,
+            "Body too large",
+        ))));
+    }
+    let addresses: Vec<String> =
+        if body.trim_start().starts_with('[') {
+            match serde_json::from_str(&body) {
+                Ok(addresses) => addresses,
+                Err(_) => {
+                    return Ok(Err(Response::with((
+                        status::BadRequest,
+                        "Invalid json array",
+                    ))))
+                }
+            }
+        } else {
+            body.lines().map(String::from).collect()
+        };
+    if addresses.len() > MAX_CHECK_ADDRESSES {
+        return Ok(Err(Response::with((
+            status::PayloadTooLarge,
+            "Too many ips",
+        ))));
+    }
+    let addresses: Vec<IpAddr> = addresses
+        .iter()
+        .filter_map(|address| address.trim().parse().ok())
+        .collect();
+
+    // Only the networks which may contain one of the addresses are
+    // loaded, split to stay below the bind parameter limits.
+    let networks: BTreeSet<Vec<u8>> = addresses
+        .iter()
+        .flat_map(|address| containing_networks(*address))
+        .collect();
+    let networks: Vec<Vec<u8>> = networks.into_iter().collect();
+    let now = Utc::now().naive_utc();
+    let allowlist_query = allowlist::table;
+    let allowed: Vec<Allowlist> =
+        req_db_op!(req, allowlist_query, load);
+    let mut values: Vec<Blacklist> = Vec::new();
+    let mut sources: Vec<Source> = Vec::new();
+    for chunk in networks.chunks(NETWORKS_PER_QUERY) {
+        let blacklist_query = blacklist.filter(
+            ip.eq_any(chunk.to_vec())
+                .and(expires.is_null().or(expires.gt(now))),
+        );
+        let chunk_values: Vec<Blacklist> =
+            req_db_op!(req, blacklist_query, load);
+        values.extend(chunk_values);
+        let source_query = blacklist_source::table.filter(
+            blacklist_source::ip.eq_any(chunk.to_vec()).and(
+                blacklist_source::expires
+                    .is_null()
+                    .or(blacklist_source::expires.gt(now)),
+            ),
+        );
+        let chunk_sources: Vec<Source> =
+            req_db_op!(req, source_query, load);
+        sources.extend(chunk_sources);
+    }
+    let index = BlacklistIndex::new(
+        values
+            .iter()
+            .filter(|value| !is_allowlisted(&allowed, value)),
+    );
+    let sources = SourceIndex::new(sources);
+
+    Ok(Ok(addresses
+        .into_iter()
+        .filter_map(|address| {
+            let entries = index.lookup(address);
+            if entries.is_empty() {
+                None
+            } else {
+                Some(CheckResult {
+                    ip: address.to_string(),
+                    entries: entries
+                        .into_iter()
+                        .flat_map(|value| sources.entries(value))
+                        .collect(),
+                })
+            }
+        })
+        .collect()))
+}
+
+/// Looks up the address given in the path. Returns the response to
+/// send instead if the address is invalid or not listed.
+fn check_address(
+    req: &mut Request,
+) -> IronResult<Result<CheckResult, Response>> {
+    let address: IpAddr = match req
+        .url
+        .path()
+        .get(2)
+        .and_then(|address| address.parse().ok())
+    {
+        Some(address) => address,
+        None => {
+            return Ok(Err(Response::with((
+                status::BadRequest,
+                "Invalid ip",
+            ))))
+        }
+    };
+    let host_prefix = max_prefix(&address);
+    let (raw_ip, raw_type) = convert_to_raw(address, host_prefix);
+    let now = Utc::now().naive_utc();
+
+    let allowlist_query = allowlist::table;
+    let allowed: Vec<Allowlist> =
+        req_db_op!(req, allowlist_query, load);
+    let blacklist_query = blacklist.filter(
+        ip_type
+            .eq(raw_type)
+            .and(ip.eq_any(containing_networks(address)))
+            .and(expires.is_null().or(expires.gt(now))),
+    );
+    let values: Vec<Blacklist> =
+        req_db_op!(req, blacklist_query, load);
+    let source_query = blacklist_source::table.filter(
+        blacklist_source::ip_type
+            .eq(raw_type)
+            .and(
+                blacklist_source::ip
+                    .eq_any(containing_networks(address)),
+            )
+            .and(
+                blacklist_source::expires
+                    .is_null()
+                    .or(blacklist_source::expires.gt(now)),
+            ),
+    );
+    let sources: Vec<Source> =
+        req_db_op!(req, source_query, load);
+    let sources = SourceIndex::new(sources);
+
+    let entries: Vec<CheckEntry> = values
+        .iter()
+        .filter(|value| {
+            networks_overlap(
+                (&value.ip, value.ip_type, value.prefix),
+                (&raw_ip, raw_type, i16::from(host_prefix)),
+            )
+        })
+        .filter(|value| !is_allowlisted(&allowed, value))
+        .flat_map(|value| sources.entries(value))
+        .collect();
+    if entries.is_empty() {
+        return Ok(Err(Response::with(status::NotFound)));
+    }
+    Ok(Ok(CheckResult {
+        ip: address.to_string(),
+        entries,
+    }))
+}
@@ -0,0 +1,125 @@
+use crate::{
+    helper::{
+        containing_networks, convert_to_raw, max_prefix,
+        method_not_allowed, negotiate, networks_overlap, Format,
+    },
+    middleware::diesel::{
+        DBType, DieselPooledConnection, DieselReqExt,
+        DieselTypeExt,
+    },
+    req_db_op,
+    schema::blacklist_history::dsl::*,
+    types::history::History,
+};
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, QueryDsl,
+    RunQueryDsl,
+};
+use iron::{
+    error::IronError, headers::ContentType, method::Method,
+    status, IronResult, Request, Response,
+};
+use log::debug;
+use std::net::IpAddr;
+
+pub fn index(req: &mut Request) -> IronResult<Response> {
+    match &req.method {
+        Method::Get => history_get(req),
+        _ => method_not_allowed(vec![Method::Get]),
+    }
+}
+
+fn history_get(req: &mut Request) -> IronResult<Response> {
+    match negotiate(req) {
+        Format::Json => history_get_json(req),
+        Format::Text => history_get_text(req),
+        Format::Default => history_get_default(req),
+    }
+}
+
+fn history_get_default(
+    req: &mut Request,
+) -> IronResult<Response> {
+    history_get_json(req)
+}
+
+fn history_get_json(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving history_get_json request");
+    let values = match load_history(req)? {
+        Ok(values) => values,
+        Err(response) => return Ok(response),
+    };
+    let json = serde_json::to_string(&values).map_err(|err| {
+        IronError::new(err, status::InternalServerError)
+    })?;
+    let mut response = Response::with((status::Ok, json));
+    response.headers.set(ContentType::json());
+    Ok(response)
+}
+
+fn history_get_text(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving history_get_text request");
+    let values = match load_history(req)? {
+        Ok(values) => values,
+        Err(response) => return Ok(response),
+    };
+    let plain = values
+        .iter()
+        .map(History::to_line)
+        .collect::<Vec<String>>()
+        .join("\n");
+    let mut response = Response::with((status::Ok, plain));
+    response.headers.set(ContentType::plaintext());
+    Ok(response)
+}
+
+/// Loads the history of every network containing the address given
+/// in the path, oldest first.
+fn load_history(
+    req: &mut Request,
+) -> IronResult<Result<Vec<History>, Response>> {
+    let address: IpAddr = match req
+        .url
+        .path()
+        .get(2)
+        .and_then(|address| address.parse().ok())
+    {
+        Some(address) => address,
+        None => {
+            return Ok(Err(Response::with((
+                status::BadRequest,
+                "Invalid ip",
+            ))))
+        }
+    };
+    let host_prefix = max_prefix(&address);
+    let (raw_ip, raw_type) = convert_to_raw(address, host_prefix);
+
+    let history_query = blacklist_history
+        .select((
+            ip,
+            ip_type,
+            prefix,
+            backend_type,
+            event,
+            score,
+            created,
+        ))
+        .filter(
+            ip_type
+                .eq(raw_type)
+                .and(ip.eq_any(containing_networks(address))),
+        )
+        .order_by(id.asc());
+    let values: Vec<History> =
+        req_db_op!(req, history_query, load);
+    Ok(Ok(values
+        .into_iter()
+        .filter(|value| {
+            networks_overlap(
+                (&value.ip, value.ip_type, value.prefix),
+                (&raw_ip, raw_type, i16::from(host_prefix)),
+            )
+        })
+        .collect()))
+}
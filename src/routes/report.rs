@@ -0,0 +1,129 @@
+use crate::{
+    helper::{max_prefix, method_not_allowed},
+    middleware::schedule::ScheduleMiddleware,
+    routes::api::store_manual,
+    types::{
+        backend_type::BackendType,
+        blacklist::Blacklist,
+        report::{
+            NewReport, Report, ReportResult, MAX_CATEGORY,
+            MAX_COMMENT_LENGTH,
+        },
+    },
+};
+use chrono::Utc;
+
+use iron::{
+    error::IronError, headers::ContentType, method::Method,
+    status, IronResult, Request, Response,
+};
+use log::debug;
+use std::{io::Read, net::IpAddr};
+
+pub fn index(req: &mut Request) -> IronResult<Response> {
+    match &req.method {
+        Method::Post => report_post(req),
+        _ => method_not_allowed(vec![Method::Post]),
+    }
+}
+
+/// Queues a report to AbuseIPDB and optionally adds the ip to the
+/// blacklist.
+fn report_post(req: &mut Request) -> IronResult<Response> {
+    debug!("Serving report_post request");
+    let reports = match req.extensions.get::<ScheduleMiddleware>()
+    {
+        Some(state) => state.reports.clone(),
+        None => {
+            return Ok(Response::with(status::ServiceUnavailable))
+        }
+    };
+    if !reports.enabled() {
+        return Ok(Response::with((
+            status::NotFound,
+            "Reporting requires abuseipdb",
+        )));
+    }
+    let new_report: NewReport = serde_json::from_reader(
+        req.body.by_ref(),
+    )
+    .map_err(|err| IronError::new(err, status::BadRequest))?;
+    let address: IpAddr = match new_report.ip.parse() {
+        Ok(address) => address,
+        Err(_) => {
+            return Ok(Response::with((
+                status::BadRequest,
+                "Invalid ip",
+            )))
+        }
+    };
+    if new_report.categories.is_empty()
+        || new_report.categories.iter().any(|category| {
+            *category == 0 || *category > MAX_CATEGORY
+        })
+    {
+        return Ok(Response::with((
+            status::BadRequest,
+            "Invalid categories",
+        )));
+    }
+    if new_report
+        .comment
+        .as_ref()
+        .map(|comment| {
+            comment.chars().count() > MAX_COMMENT_LENGTH
+        })
+        .unwrap_or(false)
+    {
+        return Ok(Response::with((
+            status::BadRequest,
+            "Comment too long",
+        )));
+    }
+
+    let blacklisted = if new_report.blacklist {
+        let mut entry = match Blacklist::new_network(
+            address,
+            max_prefix(&address),
+            BackendType::Manual,
+            new_report.comment.clone(),
+        ) {
+            Some(entry) => entry,
+            None => {
+                return Ok(Response::with((
+                    status::BadRequest,
+                    "Invalid ip",
+                )))
+            }
+        };
+        entry.expires = new_report.ttl.map(|ttl| {
+            entry.last_update
+                + chrono::Duration::seconds(i64::from(ttl))
+        });
+        store_manual(req, &entry)?;
+        true
+    } else {
+        false
+    };
+    let queued = reports.push(Report {
+        ip: address,
+        categories: new_report.categories,
+        comment: new_report.comment,
+        created: Utc::now().naive_utc(),
+    });
+
+    let result = ReportResult {
+        ip: address.to_string(),
+        queued,
+        blacklisted,
+    };
+    let json = serde_json::to_string(&result).map_err(|err| {
+        IronError::new(err, status::InternalServerError)
+    })?;
+    let mut response = Response::with((
+        if queued { status::Accepted } else { status::Ok },
+        json,
+    ));
+    response.headers.set(ContentType::json());
+    Ok(response)
+}
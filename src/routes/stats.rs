@@ -1,21 +1,24 @@
 use crate::{
-    helper::method_not_allowed,
-    middleware::diesel::{
-        DBType, DieselPooledConnection, DieselReqExt,
-        DieselTypeExt,
+    helper::{method_not_allowed, negotiate, Format},
+    middleware::{
+        diesel::{
+            DBType, DieselPooledConnection, DieselReqExt,
+            DieselTypeExt,
+        },
+        schedule::ScheduleMiddleware,
     },
     req_db_op,
-    schema::blacklist::dsl::*,
-    types::stats::IpsPerTime,
+    schema::{blacklist::dsl::*, rate_limit},
+    types::{
+        rate_limit::RateLimit,
+        stats::{IpsPerSource, IpsPerTime, NextUpdate},
+    },
 };
 use diesel::{
     query_builder::SqlQuery, sql_query, QueryDsl, RunQueryDsl,
 };
 use iron::{
-    error::IronError,
-    headers::{Accept, ContentType},
-    method::Method,
-    mime::{Mime, SubLevel, TopLevel},
+    error::IronError, headers::ContentType, method::Method,
     status, IronResult, Request, Response,
 };
 use log::debug;
@@ -26,6 +29,9 @@ pub fn index(req: &mut Request) -> IronResult<Response> {
     match req.url.path().get(1).map(|&t| t).unwrap_or("") {
         "count" => count(req),
         "countPerDay" => count_per_day(req),
+        "countPerSource" => count_per_source(req),
+        "nextUpdate" => next_update(req),
+        "rateLimit" => rate_limit(req),
         _ => Ok(Response::with(status::NotFound)),
     }
 }
@@ -38,33 +44,11 @@ fn count(req: &mut Request) -> IronResult<Response> {
 }
 
 fn count_get(req: &mut Request) -> IronResult<Response> {
-    if let Some(Accept(mimes)) = req.headers.get() {
-        let mut mimes = mimes.clone();
-        mimes.sort_by(|a, b| b.quality.cmp(&a.quality));
-        for mime in mimes {
-            match mime.item {
-                Mime(
-                    TopLevel::Application,
-                    SubLevel::Json,
-                    _,
-                )
-                | Mime(
-                    TopLevel::Application,
-                    SubLevel::Star,
-                    _,
-                )
-                | Mime(TopLevel::Star, _, _) => {
-                    return count_get_json(req)
-                }
-                Mime(TopLevel::Text, SubLevel::Plain, _)
-                | Mime(TopLevel::Text, SubLevel::Star, _) => {
-                    return count_get_text(req)
-                }
-                _ => {}
-            }
-        }
+    match negotiate(req) {
+        Format::Json => count_get_json(req),
+        Format::Text => count_get_text(req),
+        Format::Default => count_get_default(req),
     }
-    count_get_default(req)
 }
 
 fn count_get_default(req: &mut Request) -> IronResult<Response> {
@@ -102,33 +86,11 @@ fn count_per_day(req: &mut Request) -> IronResult<Response> {
 }
 
 fn count_per_day_get(req: &mut Request) -> IronResult<Response> {
-    if let Some(Accept(mimes)) = req.headers.get() {
-        let mut mimes = mimes.clone();
-        mimes.sort_by(|a, b| b.quality.cmp(&a.quality));
-        for mime in mimes {
-            match mime.item {
-                Mime(
-                    TopLevel::Application,
-                    SubLevel::Json,
-                    _,
-                )
-                | Mime(
-                    TopLevel::Application,
-                    SubLevel::Star,
-                    _,
-                )
-                | Mime(TopLevel::Star, _, _) => {
-                    return count_per_day_get_json(req)
-                }
-                Mime(TopLevel::Text, SubLevel::Plain, _)
-                | Mime(TopLevel::Text, SubLevel::Star, _) => {
-                    return count_per_day_get_text(req)
-                }
-                _ => {}
-            }
-        }
+    match negotiate(req) {
+        Format::Json => count_per_day_get_json(req),
+        Format::Text => count_per_day_get_text(req),
+        Format::Default => count_per_day_get_default(req),
     }
-    count_per_day_get_default(req)
 }
 
 fn count_per_day_query(req: &mut Request) -> SqlQuery {
@@ -213,3 +175,183 @@ fn count_per_day_get_text(
     response.headers.set(ContentType::plaintext());
     Ok(response)
 }
+
+fn count_per_source(req: &mut Request) -> IronResult<Response> {
+    match &req.method {
+        Method::Get => count_per_source_get(req),
+        _ => method_not_allowed(vec![Method::Get]),
+    }
+}
+
+fn count_per_source_get(
+    req: &mut Request,
+) -> IronResult<Response> {
+    match negotiate(req) {
+        Format::Json => count_per_source_get_json(req),
+        Format::Text => count_per_source_get_text(req),
+        Format::Default => count_per_source_get_default(req),
+    }
+}
+
+pub fn count_per_source_query() -> SqlQuery {
+    sql_query(
+        "
+        SELECT backend_type, COUNT(*) as count
+        FROM blacklist_source
+        GROUP BY backend_type
+        ORDER BY backend_type;
+    ",
+    )
+}
+
+fn count_per_source_get_default(
+    req: &mut Request,
+) -> IronResult<Response> {
+    count_per_source_get_json(req)
+}
+
+fn count_per_source_get_json(
+    req: &mut Request,
+) -> IronResult<Response> {
+    debug!("Serving count_per_source_get_json request");
+    let count_query = count_per_source_query();
+    let count: Vec<IpsPerSource> =
+        req_db_op!(req, count_query, get_results);
+    let json = serde_json::to_string(&count).map_err(|err| {
+        IronError::new(err, status::InternalServerError)
+    })?;
+    let mut response = Response::with((status::Ok, json));
+    response.headers.set(ContentType::json());
+    Ok(response)
+}
+
+fn count_per_source_get_text(
+    req: &mut Request,
+) -> IronResult<Response> {
+    debug!("Serving count_per_source_get_text request");
+    let count_query = count_per_source_query();
+    let count: Vec<IpsPerSource> =
+        req_db_op!(req, count_query, get_results);
+    let plain: String = count
+        .into_iter()
+        .map(|s| format!("{} {}", s.backend_type, s.count))
+        .collect::<Vec<String>>()
+        .join("\n");
+    let mut response = Response::with((status::Ok, plain));
+    response.headers.set(ContentType::plaintext());
+    Ok(response)
+}
+
+fn next_update(req: &mut Request) -> IronResult<Response> {
+    match &req.method {
+        Method::Get => next_update_get(req),
+        _ => method_not_allowed(vec![Method::Get]),
+    }
+}
+
+fn next_update_get(req: &mut Request) -> IronResult<Response> {
+    match negotiate(req) {
+        Format::Json => next_update_get_json(req),
+        Format::Text => next_update_get_text(req),
+        Format::Default => next_update_get_default(req),
+    }
+}
+
+fn next_update_get_default(
+    req: &mut Request,
+) -> IronResult<Response> {
+    next_update_get_json(req)
+}
+
+fn load_next_updates(req: &mut Request) -> Vec<NextUpdate> {
+    req.extensions
+        .get::<ScheduleMiddleware>()
+        .map(|state| state.next_runs.list())
+        .unwrap_or_default()
+}
+
+fn next_update_get_json(
+    req: &mut Request,
+) -> IronResult<Response> {
+    debug!("Serving next_update_get_json request");
+    let values = load_next_updates(req);
+    let json = serde_json::to_string(&values).map_err(|err| {
+        IronError::new(err, status::InternalServerError)
+    })?;
+    let mut response = Response::with((status::Ok, json));
+    response.headers.set(ContentType::json());
+    Ok(response)
+}
+
+fn next_update_get_text(
+    req: &mut Request,
+) -> IronResult<Response> {
+    debug!("Serving next_update_get_text request");
+    let plain: String = load_next_updates(req)
+        .into_iter()
+        .map(|s| format!("{} {}", s.backend_type, s.next_run))
+        .collect::<Vec<String>>()
+        .join("\n");
+    let mut response = Response::with((status::Ok, plain));
+    response.headers.set(ContentType::plaintext());
+    Ok(response)
+}
+
+fn rate_limit(req: &mut Request) -> IronResult<Response> {
+    match &req.method {
+        Method::Get => rate_limit_get(req),
+        _ => method_not_allowed(vec![Method::Get]),
+    }
+}
+
+fn rate_limit_get(req: &mut Request) -> IronResult<Response> {
+    match negotiate(req) {
+        Format::Json => rate_limit_get_json(req),
+        Format::Text => rate_limit_get_text(req),
+        Format::Default => rate_limit_get_default(req),
+    }
+}
+
+fn rate_limit_get_default(
+    req: &mut Request,
+) -> IronResult<Response> {
+    rate_limit_get_json(req)
+}
+
+fn rate_limit_get_json(
+    req: &mut Request,
+) -> IronResult<Response> {
+    debug!("Serving rate_limit_get_json request");
+    let limit_query = rate_limit::table.order_by((
+        rate_limit::backend_type,
+        rate_limit::endpoint,
+    ));
+    let values: Vec<RateLimit> =
+        req_db_op!(req, limit_query, load);
+    let json = serde_json::to_string(&values).map_err(|err| {
+        IronError::new(err, status::InternalServerError)
+    })?;
+    let mut response = Response::with((status::Ok, json));
+    response.headers.set(ContentType::json());
+    Ok(response)
+}
+
+fn rate_limit_get_text(
+    req: &mut Request,
+) -> IronResult<Response> {
+    debug!("Serving rate_limit_get_text request");
+    let limit_query = rate_limit::table.order_by((
+        rate_limit::backend_type,
+        rate_limit::endpoint,
+    ));
+    let values: Vec<RateLimit> =
+        req_db_op!(req, limit_query, load);
+    let plain: String = values
+        .iter()
+        .map(RateLimit::to_plain)
+        .collect::<Vec<String>>()
+        .join("\n");
+    let mut response = Response::with((status::Ok, plain));
+    response.headers.set(ContentType::plaintext());
+    Ok(response)
+}
@@ -1,8 +1,88 @@
 table! {
-    blacklist (ip, ip_type) {
+    blacklist (ip, ip_type, prefix) {
         ip -> Binary,
         ip_type -> Int2,
         backend_type -> Int2,
         last_update -> Timestamp,
+        prefix -> Int2,
+        reason -> Nullable<Text>,
+        expires -> Nullable<Timestamp>,
+        score -> Nullable<Int2>,
+        total_reports -> Nullable<Integer>,
+        last_reported -> Nullable<Timestamp>,
+        country_code -> Nullable<Text>,
+        usage_type -> Nullable<Text>,
+    }
+}
+
+table! {
+    allowlist (ip, ip_type, prefix) {
+        ip -> Binary,
+        ip_type -> Int2,
+        prefix -> Int2,
+        comment -> Nullable<Text>,
+        created_by -> Nullable<Text>,
+        created -> Timestamp,
+    }
+}
+
+table! {
+    api_token (name) {
+        name -> Text,
+        token_hash -> Text,
+        scope -> Int2,
+        created -> Timestamp,
+    }
+}
+
+table! {
+    blacklist_source (ip, ip_type, prefix, backend_type) {
+        ip -> Binary,
+        ip_type -> Int2,
+        prefix -> Int2,
+        backend_type -> Int2,
+        last_update -> Timestamp,
+        expires -> Nullable<Timestamp>,
+        reason -> Nullable<Text>,
+        score -> Nullable<Int2>,
+        total_reports -> Nullable<Integer>,
+        last_reported -> Nullable<Timestamp>,
+        country_code -> Nullable<Text>,
+        usage_type -> Nullable<Text>,
+    }
+}
+
+table! {
+    blacklist_change (id) {
+        id -> BigInt,
+        ip -> Binary,
+        ip_type -> Int2,
+        prefix -> Int2,
+        change_type -> Int2,
+        created -> Timestamp,
+    }
+}
+
+table! {
+    blacklist_history (id) {
+        id -> BigInt,
+        ip -> Binary,
+        ip_type -> Int2,
+        prefix -> Int2,
+        backend_type -> Int2,
+        event -> Int2,
+        score -> Nullable<Int2>,
+        created -> Timestamp,
+    }
+}
+
+table! {
+    rate_limit (backend_type, endpoint) {
+        backend_type -> Int2,
+        endpoint -> Text,
+        request_limit -> Nullable<Integer>,
+        remaining -> Nullable<Integer>,
+        reset -> Nullable<Timestamp>,
+        updated -> Timestamp,
     }
 }
@@ -0,0 +1,111 @@
+use crate::{
+    helper::{convert_network, convert_to_raw, networks_overlap},
+    schema::allowlist,
+    types::{blacklist::Blacklist, ip_type::IpType},
+};
+use chrono::Utc;
+use serde::{
+    ser::SerializeStruct, Deserialize, Serialize, Serializer,
+};
+use std::net::IpAddr;
+
+/// Networks which are never served, regardless of any provider.
+#[derive(
+    Debug,
+    Clone,
+    Hash,
+    Queryable,
+    Insertable,
+    Identifiable,
+    AsChangeset,
+)]
+#[table_name = "allowlist"]
+#[primary_key(ip, ip_type, prefix)]
+pub struct Allowlist {
+    pub ip: Vec<u8>,
+    pub ip_type: IpType,
+    pub prefix: i16,
+    pub comment: Option<String>,
+    pub created_by: Option<String>,
+    pub created: chrono::NaiveDateTime,
+}
+
+/// Request body used to create allowlist entries.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewAllowlist {
+    pub ip: String,
+    pub comment: Option<String>,
+    pub created_by: Option<String>,
+}
+
+impl Allowlist {
+    pub fn new(
+        addr: IpAddr,
+        prefix: u8,
+        comment: Option<String>,
+        created_by: Option<String>,
+    ) -> Self {
+        let (ip, ip_type) = convert_to_raw(addr, prefix);
+        Allowlist {
+            ip,
+            ip_type,
+            prefix: i16::from(prefix),
+            comment,
+            created_by,
+            created: Utc::now().naive_utc(),
+        }
+    }
+
+    /// Whether the given entry covers at least one allowlisted address.
+    pub fn overlaps(&self, entry: &Blacklist) -> bool {
+        networks_overlap(
+            (&self.ip, self.ip_type, self.prefix),
+            (&entry.ip, entry.ip_type, entry.prefix),
+        )
+    }
+
+    pub fn to_plain(&self) -> Option<String> {
+        convert_network(&self.ip, self.ip_type, self.prefix)
+    }
+}
+
+impl Serialize for Allowlist {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state =
+            serializer.serialize_struct("Allowlist", 4)?;
+        state.serialize_field("ip", &self.to_plain())?;
+        state.serialize_field("comment", &self.comment)?;
+        state.serialize_field("created_by", &self.created_by)?;
+        state.serialize_field("created", &self.created)?;
+        state.end()
+    }
+}
+
+/// Whether the given entry is covered by any of the allowlisted
+/// networks.
+pub fn is_allowlisted(
+    allowlist: &[Allowlist],
+    entry: &Blacklist,
+) -> bool {
+    allowlist.iter().any(|allowed| allowed.overlaps(entry))
+}
+
+/// Whether the given network is covered by any of the allowlisted
+/// networks.
+pub fn is_network_allowlisted(
+    allowlist: &[Allowlist],
+    network: (&[u8], IpType, i16),
+) -> bool {
+    allowlist.iter().any(|allowed| {
+        networks_overlap(
+            (&allowed.ip, allowed.ip_type, allowed.prefix),
+            network,
+        )
+    })
+}
@@ -0,0 +1,176 @@
+use crate::schema::api_token;
+use chrono::Utc;
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    serialize::{self, Output, ToSql},
+    sql_types::SmallInt,
+};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use serde::{Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::{fmt, io::Write};
+
+const TOKEN_LENGTH: usize = 40;
+
+/// Permissions granted to a token. Every scope includes the permissions
+/// of the scopes before it.
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    AsExpression,
+    FromSqlRow,
+    Hash,
+    Eq,
+)]
+#[sql_type = "SmallInt"]
+pub enum TokenScope {
+    Unknown(i16),
+    Read,
+    Write,
+    Admin,
+}
+
+impl TokenScope {
+    pub fn parse(scope: &str) -> Option<TokenScope> {
+        match scope {
+            "read" => Some(TokenScope::Read),
+            "write" => Some(TokenScope::Write),
+            "admin" => Some(TokenScope::Admin),
+            _ => None,
+        }
+    }
+
+    /// Whether this scope grants the permissions of `required`.
+    pub fn allows(self, required: TokenScope) -> bool {
+        match (self, required) {
+            (TokenScope::Unknown(_), _)
+            | (_, TokenScope::Unknown(_)) => false,
+            (granted, required) => {
+                i16::from(granted) >= i16::from(required)
+            }
+        }
+    }
+}
+
+impl From<TokenScope> for i16 {
+    fn from(scope: TokenScope) -> Self {
+        Self::from(&scope)
+    }
+}
+
+impl From<&TokenScope> for i16 {
+    fn from(scope: &TokenScope) -> Self {
+        match scope {
+            TokenScope::Read => 0,
+            TokenScope::Write => 1,
+            TokenScope::Admin => 2,
+            TokenScope::Unknown(n) => *n,
+        }
+    }
+}
+
+impl<DB> ToSql<SmallInt, DB> for TokenScope
+where
+    DB: Backend,
+    i16: ToSql<SmallInt, DB>,
+{
+    fn to_sql<W: Write>(
+        &self,
+        out: &mut Output<W, DB>,
+    ) -> serialize::Result {
+        let n: i16 = self.into();
+        n.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<SmallInt, DB> for TokenScope
+where
+    DB: Backend,
+    i16: FromSql<SmallInt, DB>,
+{
+    fn from_sql(
+        bytes: Option<&DB::RawValue>,
+    ) -> deserialize::Result<Self> {
+        match i16::from_sql(bytes)? {
+            0 => Ok(TokenScope::Read),
+            1 => Ok(TokenScope::Write),
+            2 => Ok(TokenScope::Admin),
+            n => Ok(TokenScope::Unknown(n)),
+        }
+    }
+}
+
+impl fmt::Display for TokenScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenScope::Read => write!(f, "read"),
+            TokenScope::Write => write!(f, "write"),
+            TokenScope::Admin => write!(f, "admin"),
+            TokenScope::Unknown(n) => write!(f, "unknown-{}", n),
+        }
+    }
+}
+
+impl Serialize for TokenScope {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// A token used to access the api. Only the hash of the token is
+/// stored.
+#[derive(
+    Debug,
+    Clone,
+    Hash,
+    Queryable,
+    Insertable,
+    Identifiable,
+    AsChangeset,
+)]
+#[table_name = "api_token"]
+#[primary_key(name)]
+pub struct ApiToken {
+    pub name: String,
+    pub token_hash: String,
+    pub scope: TokenScope,
+    pub created: chrono::NaiveDateTime,
+}
+
+impl ApiToken {
+    /// Creates a new random token. Returns the entry to store and the
+    /// token in plain text, which is not available afterwards.
+    pub fn generate(
+        name: &str,
+        scope: TokenScope,
+    ) -> (Self, String) {
+        let token: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LENGTH)
+            .collect();
+        let entry = ApiToken {
+            name: name.into(),
+            token_hash: hash_token(&token),
+            scope,
+            created: Utc::now().naive_utc(),
+        };
+        (entry, token)
+    }
+}
+
+/// Hex encoded sha256 hash of the given token.
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
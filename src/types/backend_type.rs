@@ -4,7 +4,12 @@ use diesel::{
     serialize::{self, Output, ToSql},
     sql_types::SmallInt,
 };
-use std::io::Write;
+use serde::{Serialize, Serializer};
+use std::{fmt, io::Write};
+
+/// Backend types starting at this id are reserved for feeds
+/// configured by the user.
+pub const FEED_OFFSET: i16 = 100;
 
 #[derive(
     Debug,
@@ -20,6 +25,10 @@ use std::io::Write;
 pub enum BackendType {
     Unknown(i16),
     AbuseIpDb,
+    SpamhausDrop,
+    SpamhausEdrop,
+    Manual,
+    Feed(i16),
 }
 
 impl From<BackendType> for i16 {
@@ -32,6 +41,10 @@ impl From<&BackendType> for i16 {
     fn from(ip_type: &BackendType) -> Self {
         match ip_type {
             BackendType::AbuseIpDb => 0,
+            BackendType::SpamhausDrop => 1,
+            BackendType::SpamhausEdrop => 2,
+            BackendType::Manual => 3,
+            BackendType::Feed(n) => *n,
             BackendType::Unknown(n) => *n,
         }
     }
@@ -61,7 +74,40 @@ where
     ) -> deserialize::Result<Self> {
         match i16::from_sql(bytes)? {
             0 => Ok(BackendType::AbuseIpDb),
+            1 => Ok(BackendType::SpamhausDrop),
+            2 => Ok(BackendType::SpamhausEdrop),
+            3 => Ok(BackendType::Manual),
+            n if n >= FEED_OFFSET => Ok(BackendType::Feed(n)),
             n => Ok(BackendType::Unknown(n)),
         }
     }
 }
+
+impl fmt::Display for BackendType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackendType::AbuseIpDb => write!(f, "abuseipdb"),
+            BackendType::SpamhausDrop => {
+                write!(f, "spamhaus-drop")
+            }
+            BackendType::SpamhausEdrop => {
+                write!(f, "spamhaus-edrop")
+            }
+            BackendType::Manual => write!(f, "manual"),
+            BackendType::Feed(n) => write!(f, "feed-{}", n),
+            BackendType::Unknown(n) => write!(f, "unknown-{}", n),
+        }
+    }
+}
+
+impl Serialize for BackendType {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
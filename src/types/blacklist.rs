@@ -1,12 +1,18 @@
 use crate::{
-    helper::convert_ip,
-    schema::blacklist,
+    helper::{
+        convert_network, convert_to_raw, max_prefix,
+        parse_network,
+    },
+    schema::{blacklist, blacklist_source},
     types::{backend_type::BackendType, ip_type::IpType},
 };
 use chrono::Utc;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Serialize, Serializer};
 use std::net::IpAddr;
 
+/// A listed network. The details are the ones of the provider which
+/// listed it first, the ones of every provider are kept by its
+/// sources.
 #[derive(
     Debug,
     Clone,
@@ -17,36 +23,80 @@ use std::net::IpAddr;
     AsChangeset,
 )]
 #[table_name = "blacklist"]
-#[primary_key(ip, ip_type)]
+#[primary_key(ip, ip_type, prefix)]
 pub struct Blacklist {
     pub ip: Vec<u8>,
     pub ip_type: IpType,
     pub backend_type: BackendType,
     pub last_update: chrono::NaiveDateTime,
+    pub prefix: i16,
+    pub reason: Option<String>,
+    pub expires: Option<chrono::NaiveDateTime>,
+    pub score: Option<i16>,
+    pub total_reports: Option<i32>,
+    pub last_reported: Option<chrono::NaiveDateTime>,
+    pub country_code: Option<String>,
+    pub usage_type: Option<String>,
+}
+
+/// Reputation of an entry as reported by a provider. Missing values
+/// keep the stored ones.
+#[derive(Debug, Clone, Default, AsChangeset)]
+#[table_name = "blacklist_source"]
+pub struct Reputation {
+    pub score: Option<i16>,
+    pub total_reports: Option<i32>,
+    pub last_reported: Option<chrono::NaiveDateTime>,
+    pub country_code: Option<String>,
+    pub usage_type: Option<String>,
+}
+
+/// Request body used to add entries manually.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewBlacklist {
+    pub ip: String,
+    /// Seconds until the entry is removed. Kept forever if missing.
+    pub ttl: Option<u32>,
+    pub reason: Option<String>,
 }
 
 impl Blacklist {
+    /// Creates an entry from a single address or a network in CIDR
+    /// notation.
     pub fn new(
         ip: &str,
         backend_type: BackendType,
     ) -> Option<Self> {
-        let (ip, ip_type) = match ip.parse() {
-            Ok(IpAddr::V4(addr)) => {
-                let octets = addr.octets().to_vec();
-                (octets, IpType::Ipv4)
-            }
-            Ok(IpAddr::V6(addr)) => {
-                let octets = addr.octets().to_vec();
-                (octets, IpType::Ipv6)
-            }
-            Err(_) => return None,
-        };
+        let (addr, prefix) = parse_network(ip)?;
+        Self::new_network(addr, prefix, backend_type, None)
+    }
+
+    /// Creates an entry covering the whole network. Host bits of the
+    /// given address are cleared.
+    pub fn new_network(
+        addr: IpAddr,
+        prefix: u8,
+        backend_type: BackendType,
+        reason: Option<String>,
+    ) -> Option<Self> {
+        if prefix > max_prefix(&addr) {
+            return None;
+        }
+        let (ip, ip_type) = convert_to_raw(addr, prefix);
         let last_update = Utc::now().naive_utc();
         Some(Blacklist {
             ip,
             ip_type,
             backend_type,
             last_update,
+            prefix: i16::from(prefix),
+            reason,
+            expires: None,
+            score: None,
+            total_reports: None,
+            last_reported: None,
+            country_code: None,
+            usage_type: None,
         })
     }
 }
@@ -59,7 +109,7 @@ impl Serialize for Blacklist {
     where
         S: Serializer,
     {
-        match convert_ip(&self.ip, self.ip_type) {
+        match self.to_plain() {
             Some(ref value) => serializer.serialize_some(value),
             None => serializer.serialize_none(),
         }
@@ -68,8 +118,6 @@ impl Serialize for Blacklist {
 
 impl Blacklist {
     pub fn to_plain(&self) -> Option<String> {
-        convert_ip(&self.ip, self.ip_type)
-            .as_ref()
-            .map(IpAddr::to_string)
+        convert_network(&self.ip, self.ip_type, self.prefix)
     }
 }
@@ -0,0 +1,178 @@
+use crate::{
+    helper::convert_network, schema::blacklist_change,
+    types::ip_type::IpType,
+};
+use chrono::Utc;
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    serialize::{self, Output, ToSql},
+    sql_types::SmallInt,
+};
+use serde::Serialize;
+use std::{collections::HashSet, io::Write};
+
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    AsExpression,
+    FromSqlRow,
+    Hash,
+    Eq,
+)]
+#[sql_type = "SmallInt"]
+pub enum ChangeType {
+    Unknown(i16),
+    Added,
+    Removed,
+}
+
+impl From<ChangeType> for i16 {
+    fn from(change_type: ChangeType) -> Self {
+        Self::from(&change_type)
+    }
+}
+
+impl From<&ChangeType> for i16 {
+    fn from(change_type: &ChangeType) -> Self {
+        match change_type {
+            ChangeType::Added => 0,
+            ChangeType::Removed => 1,
+            ChangeType::Unknown(n) => *n,
+        }
+    }
+}
+
+impl<DB> ToSql<SmallInt, DB> for ChangeType
+where
+    DB: Backend,
+    i16: ToSql<SmallInt, DB>,
+{
+    fn to_sql<W: Write>(
+        &self,
+        out: &mut Output<W, DB>,
+    ) -> serialize::Result {
+        let n: i16 = self.into();
+        n.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<SmallInt, DB> for ChangeType
+where
+    DB: Backend,
+    i16: FromSql<SmallInt, DB>,
+{
+    fn from_sql(
+        bytes: Option<&DB::RawValue>,
+    ) -> deserialize::Result<Self> {
+        match i16::from_sql(bytes)? {
+            0 => Ok(ChangeType::Added),
+            1 => Ok(ChangeType::Removed),
+            n => Ok(ChangeType::Unknown(n)),
+        }
+    }
+}
+
+/// An entry added to or removed from the served blacklist. The id is
+/// used as cursor by clients syncing incrementally.
+#[derive(Debug, Clone, Queryable)]
+pub struct Change {
+    pub id: i64,
+    pub ip: Vec<u8>,
+    pub ip_type: IpType,
+    pub prefix: i16,
+    pub change_type: ChangeType,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "blacklist_change"]
+pub struct NewChange {
+    pub ip: Vec<u8>,
+    pub ip_type: IpType,
+    pub prefix: i16,
+    pub change_type: ChangeType,
+    pub created: chrono::NaiveDateTime,
+}
+
+impl NewChange {
+    pub fn new(
+        ip: &[u8],
+        ip_type: IpType,
+        prefix: i16,
+        change_type: ChangeType,
+    ) -> Self {
+        NewChange {
+            ip: ip.to_vec(),
+            ip_type,
+            prefix,
+            change_type,
+            created: Utc::now().naive_utc(),
+        }
+    }
+}
+
+/// Changes since the cursor given by the client, reduced to the last
+/// change of every network.
+#[derive(Debug, Clone, Serialize)]
+pub struct Changes {
+    pub cursor: i64,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl Changes {
+    /// Reduces the given changes, ordered by id, to the last change of
+    /// every network.
+    pub fn new(cursor: i64, changes: &[Change]) -> Self {
+        let mut seen = HashSet::new();
+        let mut last = Vec::new();
+        for change in changes.iter().rev() {
+            if seen.insert((
+                &change.ip,
+                change.ip_type,
+                change.prefix,
+            )) {
+                last.push(change);
+            }
+        }
+        last.reverse();
+
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        for change in last {
+            let network = match convert_network(
+                &change.ip,
+                change.ip_type,
+                change.prefix,
+            ) {
+                Some(network) => network,
+                None => continue,
+            };
+            match change.change_type {
+                ChangeType::Added => added.push(network),
+                ChangeType::Removed => removed.push(network),
+                ChangeType::Unknown(_) => {}
+            }
+        }
+        Changes {
+            cursor,
+            added,
+            removed,
+        }
+    }
+
+    pub fn to_plain(&self) -> String {
+        self.added
+            .iter()
+            .map(|network| format!("+{}", network))
+            .chain(
+                self.removed
+                    .iter()
+                    .map(|network| format!("-{}", network)),
+            )
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{
+    helper::{convert_to_raw, max_prefix},
+    types::{
+        backend_type::BackendType, blacklist::Blacklist,
+        ip_type::IpType, source::Source,
+    },
+};
+use serde::Serialize;
+use std::{collections::HashMap, net::IpAddr};
+
+/// Maximum number of addresses of a bulk check.
+pub const MAX_CHECK_ADDRESSES: usize = 1000;
+/// Maximum body size of a bulk check in bytes.
+pub const MAX_CHECK_BODY_SIZE: u64 = 64 * 1024;
+
+/// A source of a blacklist entry matching a looked up address.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckEntry {
+    pub network: Option<String>,
+    pub prefix: i16,
+    pub backend_type: BackendType,
+    pub last_update: chrono::NaiveDateTime,
+    pub reason: Option<String>,
+    pub expires: Option<chrono::NaiveDateTime>,
+    pub score: Option<i16>,
+    pub total_reports: Option<i32>,
+    pub last_reported: Option<chrono::NaiveDateTime>,
+    pub country_code: Option<String>,
+    pub usage_type: Option<String>,
+}
+
+/// Every source listing the looked up address.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub ip: String,
+    pub entries: Vec<CheckEntry>,
+}
+
+impl From<&Blacklist> for CheckEntry {
+    fn from(entry: &Blacklist) -> Self {
+        CheckEntry {
+            network: entry.to_plain(),
+            prefix: entry.prefix,
+            backend_type: entry.backend_type,
+            last_update: entry.last_update,
+            reason: entry.reason.clone(),
+            expires: entry.expires,
+            score: entry.score,
+            total_reports: entry.total_reports,
+            last_reported: entry.last_reported,
+            country_code: entry.country_code.clone(),
+            usage_type: entry.usage_type.clone(),
+        }
+    }
+}
+
+impl From<&Source> for CheckEntry {
+    fn from(source: &Source) -> Self {
+        CheckEntry {
+            network: source.to_plain(),
+            prefix: source.prefix,
+            backend_type: source.backend_type,
+            last_update: source.last_update,
+            reason: source.reason.clone(),
+            expires: source.expires,
+            score: source.score,
+            total_reports: source.total_reports,
+            last_reported: source.last_reported,
+            country_code: source.country_code.clone(),
+            usage_type: source.usage_type.clone(),
+        }
+    }
+}
+
+/// Sources of the blacklist entries grouped by network.
+pub struct SourceIndex {
+    sources: HashMap<(IpType, i16, Vec<u8>), Vec<Source>>,
+}
+
+impl SourceIndex {
+    pub fn new<I>(sources: I) -> Self
+    where
+        I: IntoIterator<Item = Source>,
+    {
+        let mut index: HashMap<_, Vec<Source>> = HashMap::new();
+        for source in sources {
+            index
+                .entry((
+                    source.ip_type,
+                    source.prefix,
+                    source.ip.clone(),
+                ))
+                .or_default()
+                .push(source);
+        }
+        SourceIndex { sources: index }
+    }
+
+    /// Returns one entry per source of the given network. Networks
+    /// without sources are returned as stored.
+    pub fn entries(&self, value: &Blacklist) -> Vec<CheckEntry> {
+        match self.sources.get(&(
+            value.ip_type,
+            value.prefix,
+            value.ip.clone(),
+        )) {
+            Some(sources) => {
+                sources.iter().map(CheckEntry::from).collect()
+            }
+            None => vec![CheckEntry::from(value)],
+        }
+    }
+}
+
+impl CheckResult {
+    pub fn to_plain(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{} {} {} {} {}",
+                    self.ip,
+                    entry
+                        .network
+                        .as_ref()
+                        .map(|s| &s[..])
+                        .unwrap_or("-"),
+                    entry.backend_type,
+                    entry.last_update,
+                    entry
+                        .reason
+                        .as_ref()
+                        .map(|s| &s[..])
+                        .unwrap_or("-"),
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// In-memory index of blacklist entries used to look up many addresses
+/// at once.
+pub struct BlacklistIndex<'a> {
+    prefixes: Vec<(IpType, i16)>,
+    entries: HashMap<(IpType, i16, Vec<u8>), Vec<&'a Blacklist>>,
+}
+
+impl<'a> BlacklistIndex<'a> {
+    pub fn new<I>(values: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Blacklist>,
+    {
+        let mut entries: HashMap<_, Vec<&'a Blacklist>> =
+            HashMap::new();
+        for value in values {
+            entries
+                .entry((
+                    value.ip_type,
+                    value.prefix,
+                    value.ip.clone(),
+                ))
+                .or_default()
+                .push(value);
+        }
+        let mut prefixes: Vec<(IpType, i16)> = entries
+            .keys()
+            .map(|(ip_type, prefix, _)| (*ip_type, *prefix))
+            .collect();
+        prefixes.sort_by_key(|(ip_type, prefix)| {
+            (i16::from(ip_type), *prefix)
+        });
+        prefixes.dedup();
+        BlacklistIndex { prefixes, entries }
+    }
+
+    /// Returns every entry containing the given address.
+    pub fn lookup(&self, address: IpAddr) -> Vec<&'a Blacklist> {
+        let host_prefix = i16::from(max_prefix(&address));
+        let (_, raw_type) = convert_to_raw(address, 0);
+        self.prefixes
+            .iter()
+            .filter(|(ip_type, prefix)| {
+                *ip_type == raw_type
+                    && *prefix >= 0
+                    && *prefix <= host_prefix
+            })
+            .filter_map(|(ip_type, prefix)| {
+                let (raw_ip, _) =
+                    convert_to_raw(address, *prefix as u8);
+                self.entries.get(&(*ip_type, *prefix, raw_ip))
+            })
+            .flatten()
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{
+    helper::convert_network,
+    schema::blacklist_history,
+    types::{
+        backend_type::BackendType, ip_type::IpType,
+        source::Source,
+    },
+};
+use chrono::Utc;
+use diesel::{
+    backend::Backend,
+    deserialize::{self, FromSql},
+    serialize::{self, Output, ToSql},
+    sql_types::SmallInt,
+};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use std::{fmt, io::Write};
+
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    AsExpression,
+    FromSqlRow,
+    Hash,
+    Eq,
+)]
+#[sql_type = "SmallInt"]
+pub enum HistoryEvent {
+    Unknown(i16),
+    /// Listed by a provider for the first time.
+    Added,
+    /// Listed again by a provider on update.
+    Refreshed,
+    /// Still listed on recheck.
+    RecheckPassed,
+    /// No longer listed on recheck and removed.
+    RecheckFailed,
+    /// Removed after not being updated for the stale duration.
+    StaleDeleted,
+    /// Removed after its expiration time.
+    Expired,
+    /// Removed manually.
+    Removed,
+}
+
+impl From<HistoryEvent> for i16 {
+    fn from(event: HistoryEvent) -> Self {
+        Self::from(&event)
+    }
+}
+
+impl From<&HistoryEvent> for i16 {
+    fn from(event: &HistoryEvent) -> Self {
+        match event {
+            HistoryEvent::Added => 0,
+            HistoryEvent::Refreshed => 1,
+            HistoryEvent::RecheckPassed => 2,
+            HistoryEvent::RecheckFailed => 3,
+            HistoryEvent::StaleDeleted => 4,
+            HistoryEvent::Expired => 5,
+            HistoryEvent::Removed => 6,
+            HistoryEvent::Unknown(n) => *n,
+        }
+    }
+}
+
+impl<DB> ToSql<SmallInt, DB> for HistoryEvent
+where
+    DB: Backend,
+    i16: ToSql<SmallInt, DB>,
+{
+    fn to_sql<W: Write>(
+        &self,
+        out: &mut Output<W, DB>,
+    ) -> serialize::Result {
+        let n: i16 = self.into();
+        n.to_sql(out)
+    }
+}
+
+impl<DB> FromSql<SmallInt, DB> for HistoryEvent
+where
+    DB: Backend,
+    i16: FromSql<SmallInt, DB>,
+{
+    fn from_sql(
+        bytes: Option<&DB::RawValue>,
+    ) -> deserialize::Result<Self> {
+        match i16::from_sql(bytes)? {
+            0 => Ok(HistoryEvent::Added),
+            1 => Ok(HistoryEvent::Refreshed),
+            2 => Ok(HistoryEvent::RecheckPassed),
+            3 => Ok(HistoryEvent::RecheckFailed),
+            4 => Ok(HistoryEvent::StaleDeleted),
+            5 => Ok(HistoryEvent::Expired),
+            6 => Ok(HistoryEvent::Removed),
+            n => Ok(HistoryEvent::Unknown(n)),
+        }
+    }
+}
+
+impl fmt::Display for HistoryEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HistoryEvent::Added => write!(f, "added"),
+            HistoryEvent::Refreshed => write!(f, "refreshed"),
+            HistoryEvent::RecheckPassed => {
+                write!(f, "recheck-passed")
+            }
+            HistoryEvent::RecheckFailed => {
+                write!(f, "recheck-failed")
+            }
+            HistoryEvent::StaleDeleted => {
+                write!(f, "stale-deleted")
+            }
+            HistoryEvent::Expired => write!(f, "expired"),
+            HistoryEvent::Removed => write!(f, "removed"),
+            HistoryEvent::Unknown(n) => {
+                write!(f, "unknown-{}", n)
+            }
+        }
+    }
+}
+
+impl Serialize for HistoryEvent {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// An append-only record of something which happened to an entry.
+#[derive(Debug, Clone, Queryable)]
+pub struct History {
+    pub ip: Vec<u8>,
+    pub ip_type: IpType,
+    pub prefix: i16,
+    pub backend_type: BackendType,
+    pub event: HistoryEvent,
+    pub score: Option<i16>,
+    pub created: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[table_name = "blacklist_history"]
+pub struct NewHistory {
+    pub ip: Vec<u8>,
+    pub ip_type: IpType,
+    pub prefix: i16,
+    pub backend_type: BackendType,
+    pub event: HistoryEvent,
+    pub score: Option<i16>,
+    pub created: chrono::NaiveDateTime,
+}
+
+impl NewHistory {
+    pub fn new(
+        source: &Source,
+        event: HistoryEvent,
+        score: Option<i16>,
+    ) -> Self {
+        NewHistory {
+            ip: source.ip.clone(),
+            ip_type: source.ip_type,
+            prefix: source.prefix,
+            backend_type: source.backend_type,
+            event,
+            score,
+            created: Utc::now().naive_utc(),
+        }
+    }
+}
+
+impl Serialize for History {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state =
+            serializer.serialize_struct("History", 5)?;
+        state.serialize_field("network", &self.to_plain())?;
+        state.serialize_field(
+            "backend_type",
+            &self.backend_type,
+        )?;
+        state.serialize_field("event", &self.event)?;
+        state.serialize_field("score", &self.score)?;
+        state.serialize_field("created", &self.created)?;
+        state.end()
+    }
+}
+
+impl History {
+    pub fn to_plain(&self) -> Option<String> {
+        convert_network(&self.ip, self.ip_type, self.prefix)
+    }
+
+    /// Formats the record as `created network backend event [score]`.
+    pub fn to_line(&self) -> String {
+        let mut line = format!(
+            "{} {} {} {}",
+            self.created,
+            self.to_plain().unwrap_or_default(),
+            self.backend_type,
+            self.event
+        );
+        if let Some(score) = self.score {
+            line.push_str(&format!(" {}", score));
+        }
+        line
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{
+    schema::rate_limit, types::backend_type::BackendType,
+};
+use chrono::{Duration, NaiveDateTime, Utc};
+use serde::Serialize;
+
+/// Requests kept back from every budget.
+pub const RATE_LIMIT_RESERVE: i32 = 2;
+
+/// Request budget of a provider endpoint as reported by the provider.
+/// Persisted so exhausted budgets are respected after a restart.
+#[derive(Debug, Clone, Queryable, Insertable, Serialize)]
+#[table_name = "rate_limit"]
+pub struct RateLimit {
+    pub backend_type: BackendType,
+    pub endpoint: String,
+    /// Requests allowed until the reset.
+    pub request_limit: Option<i32>,
+    /// Requests left until the reset.
+    pub remaining: Option<i32>,
+    pub reset: Option<NaiveDateTime>,
+    pub updated: NaiveDateTime,
+}
+
+impl RateLimit {
+    pub fn new(
+        backend_type: BackendType,
+        endpoint: &str,
+    ) -> Self {
+        RateLimit {
+            backend_type,
+            endpoint: endpoint.into(),
+            request_limit: None,
+            remaining: None,
+            reset: None,
+            updated: Utc::now().naive_utc(),
+        }
+    }
+
+    /// Whether no request is left, apart from a small reserve which
+    /// avoids running into the limit. The budget is available again
+    /// once the reset time passed. Without a reset time, a day is
+    /// assumed.
+    pub fn exhausted(&self, now: NaiveDateTime) -> bool {
+        let reset = self
+            .reset
+            .unwrap_or_else(|| self.updated + Duration::days(1));
+        match self.remaining {
+            Some(remaining) => {
+                remaining <= RATE_LIMIT_RESERVE && now < reset
+            }
+            None => false,
+        }
+    }
+
+    pub fn to_plain(&self) -> String {
+        format!(
+            "{} {} {} {} {}",
+            self.backend_type,
+            self.endpoint,
+            self.remaining
+                .map(|remaining| remaining.to_string())
+                .unwrap_or_else(|| "-".into()),
+            self.request_limit
+                .map(|limit| limit.to_string())
+                .unwrap_or_else(|| "-".into()),
+            self.reset
+                .map(|reset| reset.to_string())
+                .unwrap_or_else(|| "-".into()),
+        )
+    }
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Highest category id known to AbuseIPDB.
+pub const MAX_CATEGORY: u8 = 23;
+/// Maximum length of a comment accepted by AbuseIPDB.
+pub const MAX_COMMENT_LENGTH: usize = 1024;
+
+/// Request body used to report an ip.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewReport {
+    pub ip: String,
+    /// AbuseIPDB category ids, see
+    /// <https://www.abuseipdb.com/categories>.
+    pub categories: Vec<u8>,
+    pub comment: Option<String>,
+    /// Whether the ip is added to the blacklist as a manual entry.
+    #[serde(default)]
+    pub blacklist: bool,
+    /// Seconds until the manual entry is removed. Kept forever if
+    /// missing.
+    pub ttl: Option<u32>,
+}
+
+/// A report waiting to be forwarded to AbuseIPDB.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub ip: IpAddr,
+    pub categories: Vec<u8>,
+    pub comment: Option<String>,
+    pub created: chrono::NaiveDateTime,
+}
+
+/// Response to a report.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportResult {
+    pub ip: String,
+    /// Whether the report was queued. Reports of an ip already
+    /// reported within the last 15 minutes are dropped.
+    pub queued: bool,
+    /// Whether the ip was added to the blacklist.
+    pub blacklisted: bool,
+}
@@ -0,0 +1,62 @@
+use crate::{
+    helper::convert_network,
+    schema::blacklist_source,
+    types::{
+        backend_type::BackendType, blacklist::Blacklist,
+        ip_type::IpType,
+    },
+};
+
+/// A provider listing an entry. An entry stays listed as long as at
+/// least one of its sources remains. Details like the reason or the
+/// reputation are kept per provider.
+#[derive(
+    Debug,
+    Clone,
+    Hash,
+    Queryable,
+    Insertable,
+    Identifiable,
+    AsChangeset,
+)]
+#[table_name = "blacklist_source"]
+#[primary_key(ip, ip_type, prefix, backend_type)]
+pub struct Source {
+    pub ip: Vec<u8>,
+    pub ip_type: IpType,
+    pub prefix: i16,
+    pub backend_type: BackendType,
+    pub last_update: chrono::NaiveDateTime,
+    pub expires: Option<chrono::NaiveDateTime>,
+    pub reason: Option<String>,
+    pub score: Option<i16>,
+    pub total_reports: Option<i32>,
+    pub last_reported: Option<chrono::NaiveDateTime>,
+    pub country_code: Option<String>,
+    pub usage_type: Option<String>,
+}
+
+impl From<&Blacklist> for Source {
+    fn from(entry: &Blacklist) -> Self {
+        Source {
+            ip: entry.ip.clone(),
+            ip_type: entry.ip_type,
+            prefix: entry.prefix,
+            backend_type: entry.backend_type,
+            last_update: entry.last_update,
+            expires: entry.expires,
+            reason: entry.reason.clone(),
+            score: entry.score,
+            total_reports: entry.total_reports,
+            last_reported: entry.last_reported,
+            country_code: entry.country_code.clone(),
+            usage_type: entry.usage_type.clone(),
+        }
+    }
+}
+
+impl Source {
+    pub fn to_plain(&self) -> Option<String> {
+        convert_network(&self.ip, self.ip_type, self.prefix)
+    }
+}
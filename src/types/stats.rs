@@ -1,4 +1,5 @@
-use diesel::sql_types::{BigInt, Timestamp};
+use crate::types::backend_type::BackendType;
+use diesel::sql_types::{BigInt, SmallInt, Timestamp};
 use serde::Serialize;
 
 #[derive(Debug, Clone, QueryableByName, Serialize)]
@@ -10,3 +11,72 @@ pub struct IpsPerTime {
     #[sql_type = "Timestamp"]
     pub last_update_end: chrono::NaiveDateTime,
 }
+
+#[derive(Debug, Clone, QueryableByName, Serialize)]
+pub struct IpsPerSource {
+    #[sql_type = "SmallInt"]
+    pub backend_type: BackendType,
+    #[sql_type = "BigInt"]
+    pub count: i64,
+}
+
+/// The next planned update of a provider.
+#[derive(Debug, Clone, Serialize)]
+pub struct NextUpdate {
+    pub backend_type: BackendType,
+    pub name: String,
+    pub next_run: chrono::NaiveDateTime,
+}
+
+/// A run of the scheduled or manually triggered update.
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateRun {
+    pub providers: Vec<BackendType>,
+    pub started: chrono::NaiveDateTime,
+    pub finished: Option<chrono::NaiveDateTime>,
+    pub inserted: usize,
+    pub refreshed: usize,
+    pub deleted: usize,
+    pub errors: usize,
+}
+
+/// The state of the update returned by the admin api.
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateStatus {
+    pub current: Option<UpdateRun>,
+    pub last: Option<UpdateRun>,
+    /// Providers which are due but not yet updated.
+    pub queued: Vec<NextUpdate>,
+}
+
+impl UpdateStatus {
+    pub fn to_plain(&self) -> String {
+        let runs = vec![
+            ("current", &self.current),
+            ("last", &self.last),
+        ];
+        runs.into_iter()
+            .filter_map(|(label, run)| {
+                run.as_ref().map(|run| (label, run))
+            })
+            .map(|(label, run)| {
+                format!(
+                    "{} {} {} {} inserted, {} refreshed, {} deleted, {} errors",
+                    label,
+                    run.started,
+                    run.finished
+                        .map(|finished| finished.to_string())
+                        .unwrap_or_else(|| "-".into()),
+                    run.inserted,
+                    run.refreshed,
+                    run.deleted,
+                    run.errors,
+                )
+            })
+            .chain(self.queued.iter().map(|value| {
+                format!("queued {} {}", value.backend_type, value.next_run)
+            }))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}